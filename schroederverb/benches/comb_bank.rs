@@ -0,0 +1,110 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use euterpe_rs::processor::AudioProcessor;
+use schroederverb::block::{BlockProcessor, MAX_BLOCK_SIZE};
+use schroederverb::comb_bank::CombBank;
+use schroederverb::delay_line::Interpolation;
+use schroederverb::float::Float;
+use schroederverb::mod_comb::ModComb;
+
+const SAMPLE_RATE: f64 = 44100.0;
+const NUM_COMBS: usize = 8;
+/// The Schroeder comb lengths.
+const COMB_DELAYS_MS: [f64; NUM_COMBS] = [29.7, 31.4, 32.2, 35.6, 38.1, 40.9, 45.6, 48.5];
+const MAX_DELAY_SAMPLES: usize = 4631;
+const NUM_SAMPLES: usize = 4096;
+
+fn delay_samples(lane: usize) -> f64 {
+    (COMB_DELAYS_MS[lane] * SAMPLE_RATE / 1000.0).round()
+}
+
+/// Same sequence as the tests' `test_util::noise`, which benches cannot
+/// reach since it only exists under `cfg(test)`.
+fn input<T: Float>() -> Vec<T> {
+    let mut seed: u32 = 1;
+    (0..NUM_SAMPLES)
+        .map(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            T::from_f64((seed as f64 / u32::MAX as f64) * 2.0 - 1.0)
+        })
+        .collect()
+}
+
+fn mod_combs<T: Float>(depth_samples: f64) -> [ModComb<T>; NUM_COMBS] {
+    std::array::from_fn(|lane| {
+        let mut comb = ModComb::new(MAX_DELAY_SAMPLES, Interpolation::CubicHermite, SAMPLE_RATE);
+        comb.prepare(delay_samples(lane));
+        comb.set_gain(0.8);
+        comb.set_dampening(0.3);
+        comb.set_modulation(depth_samples, 0.5 + 0.1 * lane as f64);
+        comb
+    })
+}
+
+fn comb_bank<T: Float>(depth_samples: f64) -> CombBank<T, NUM_COMBS> {
+    let mut bank = CombBank::new(MAX_DELAY_SAMPLES, Interpolation::CubicHermite, SAMPLE_RATE);
+    for lane in 0..NUM_COMBS {
+        bank.prepare(lane, delay_samples(lane));
+        bank.set_gain(lane, 0.8);
+        bank.set_dampening(lane, 0.3);
+        bank.set_modulation(lane, depth_samples, 0.5 + 0.1 * lane as f64);
+    }
+    bank
+}
+
+/// Eight combs on the same input, summed, as `Schroeder` runs them.
+fn bench_combs<T: Float>(c: &mut Criterion, type_name: &str) {
+    let input = input::<T>();
+    let mut group = c.benchmark_group(format!("combs_{}", type_name));
+    group.throughput(Throughput::Elements(NUM_SAMPLES as u64));
+    for (name, depth_samples) in [("static", 0.0), ("modulated", 2.0)] {
+        let mut combs = mod_combs::<T>(depth_samples);
+        group.bench_function(BenchmarkId::new("mod_comb", name), |b| {
+            b.iter(|| {
+                let mut sum = T::ZERO;
+                for &sample in &input {
+                    for comb in combs.iter_mut() {
+                        sum += comb.process(black_box(sample));
+                    }
+                }
+                sum
+            })
+        });
+
+        let mut combs = mod_combs::<T>(depth_samples);
+        group.bench_function(BenchmarkId::new("mod_comb_block", name), |b| {
+            let mut output = [T::ZERO; MAX_BLOCK_SIZE];
+            b.iter(|| {
+                let mut sum = T::ZERO;
+                for block in input.chunks(MAX_BLOCK_SIZE) {
+                    for comb in combs.iter_mut() {
+                        comb.process_block(black_box(block), &mut output[..block.len()]);
+                        sum += output[0];
+                    }
+                }
+                sum
+            })
+        });
+
+        let mut bank = comb_bank::<T>(depth_samples);
+        group.bench_function(BenchmarkId::new("comb_bank", name), |b| {
+            b.iter(|| {
+                let mut sum = T::ZERO;
+                for &sample in &input {
+                    for out in bank.process(black_box(sample)) {
+                        sum += out;
+                    }
+                }
+                sum
+            })
+        });
+    }
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    bench_combs::<f32>(c, "f32");
+    bench_combs::<f64>(c, "f64");
+}
+
+criterion_group!(comb_bank_benches, benches);
+criterion_main!(comb_bank_benches);
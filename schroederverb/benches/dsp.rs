@@ -0,0 +1,157 @@
+//! Throughput of the DSP chain: the whole stereo reverb per block for each
+//! algorithm, the Schroeder network per sample and per block, and each of its
+//! stages on its own. `cargo xtask bench-check` compares a run against a
+//! baseline saved with `cargo xtask bench-baseline`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use euterpe_rs::processor::AudioProcessor;
+use schroederverb::all_pass::AllPass;
+use schroederverb::block::{BlockProcessor, MAX_BLOCK_SIZE};
+use schroederverb::delay_line::Interpolation;
+use schroederverb::mod_all_pass::ModAllPass;
+use schroederverb::mod_comb::ModComb;
+use schroederverb::reverb::Algorithm;
+use schroederverb::schroeder::Schroeder;
+use schroederverb::stereo::StereoReverb;
+
+const SAMPLE_RATES: [f64; 2] = [44100.0, 96000.0];
+const NUM_SAMPLES: usize = 4096;
+const RT60_MS: f64 = 2000.0;
+
+/// Same sequence as the tests' `test_util::noise`, which benches cannot
+/// reach since it only exists under `cfg(test)`.
+fn noise() -> Vec<f64> {
+    let mut seed: u32 = 1;
+    (0..NUM_SAMPLES)
+        .map(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed as f64 / u32::MAX as f64) * 2.0 - 1.0
+        })
+        .collect()
+}
+
+/// The network as the plugin runs it, with both kinds of modulation on.
+fn schroeder(sample_rate: f64) -> Schroeder {
+    let mut schroeder = Schroeder::new(sample_rate);
+    schroeder.prepare(sample_rate, RT60_MS);
+    schroeder.set_dampening(0.3);
+    schroeder.set_mod_enabled(true);
+    schroeder.set_comb_modulation(0.5, 0.7);
+    schroeder
+}
+
+fn bench_schroeder(c: &mut Criterion) {
+    let input = noise();
+    let mut group = c.benchmark_group("schroeder");
+    group.throughput(Throughput::Elements(NUM_SAMPLES as u64));
+    for sample_rate in SAMPLE_RATES {
+        let mut uut = schroeder(sample_rate);
+        group.bench_function(BenchmarkId::new("per_sample", sample_rate), |b| {
+            b.iter(|| input.iter().fold(0.0, |sum, sample| sum + uut.process(black_box(*sample))))
+        });
+
+        let mut uut = schroeder(sample_rate);
+        let mut output = [0.0; MAX_BLOCK_SIZE];
+        group.bench_function(BenchmarkId::new("per_block", sample_rate), |b| {
+            b.iter(|| {
+                input.chunks(MAX_BLOCK_SIZE).fold(0.0, |sum, block| {
+                    uut.process_block(black_box(block), &mut output[..block.len()]);
+                    sum + output[0]
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+/// The whole chain as the plugin runs it, in `f32`: pre-delay, `algorithm`,
+/// early reflections, EQ and ducking all engaged.
+fn stereo_reverb(sample_rate: f64, algorithm: Algorithm) -> StereoReverb<f32> {
+    let mut reverb = StereoReverb::new(sample_rate);
+    reverb.prepare(sample_rate, RT60_MS);
+    reverb.set_algorithm(algorithm);
+    // Skips the crossfade from the default algorithm.
+    reverb.reset();
+    reverb.set_pre_delay(20.0);
+    reverb.set_dampening(0.3);
+    reverb.set_band_decay(1.5, 0.5);
+    reverb.set_er_balance(0.3);
+    reverb.set_low_cut(100.0);
+    reverb.set_high_cut(8000.0);
+    reverb.set_tilt(-2.0);
+    reverb.set_ducking(0.5, 10.0, 250.0);
+    reverb.set_stereo_spread(0.5);
+    reverb.set_mod_enabled(true);
+    reverb.set_comb_modulation(0.5, 0.7);
+    reverb
+}
+
+fn bench_stereo_reverb(c: &mut Criterion) {
+    let input: Vec<f32> = noise().into_iter().map(|sample| sample as f32).collect();
+    let key: Vec<f32> = input.iter().map(|sample| sample.abs()).collect();
+    let mut group = c.benchmark_group("stereo_reverb");
+    group.throughput(Throughput::Elements(NUM_SAMPLES as u64));
+    for algorithm in [Algorithm::Schroeder, Algorithm::Moorer, Algorithm::Freeverb, Algorithm::Fdn] {
+        let mut uut = stereo_reverb(SAMPLE_RATES[0], algorithm);
+        let (mut left, mut right) = ([0.0; MAX_BLOCK_SIZE], [0.0; MAX_BLOCK_SIZE]);
+        group.bench_function(BenchmarkId::new("per_block", format!("{:?}", algorithm)), |b| {
+            b.iter(|| {
+                input.chunks(MAX_BLOCK_SIZE).zip(key.chunks(MAX_BLOCK_SIZE)).fold(0.0, |sum, (block, key)| {
+                    let (left, right) = (&mut left[..block.len()], &mut right[..block.len()]);
+                    left.copy_from_slice(black_box(block));
+                    right.copy_from_slice(black_box(block));
+                    uut.process_block(left, right, key);
+                    sum + left[0] + right[0]
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Per-sample and per-block runs of one stage.
+fn bench_stage<P: BlockProcessor<f64>>(c: &mut Criterion, name: &str, mut new: impl FnMut() -> P) {
+    let input = noise();
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(NUM_SAMPLES as u64));
+    let mut uut = new();
+    group.bench_function("per_sample", |b| {
+        b.iter(|| input.iter().fold(0.0, |sum, sample| sum + uut.process(black_box(*sample))))
+    });
+
+    let mut uut = new();
+    let mut output = [0.0; MAX_BLOCK_SIZE];
+    group.bench_function("per_block", |b| {
+        b.iter(|| {
+            input.chunks(MAX_BLOCK_SIZE).fold(0.0, |sum, block| {
+                uut.process_block(black_box(block), &mut output[..block.len()]);
+                sum + output[0]
+            })
+        })
+    });
+    group.finish();
+}
+
+/// The Schroeder stages at 44.1 kHz, at typical lengths.
+fn bench_stages(c: &mut Criterion) {
+    let sample_rate = SAMPLE_RATES[0];
+    bench_stage(c, "mod_comb", || {
+        let mut comb = ModComb::new(4631, Interpolation::CubicHermite, sample_rate);
+        comb.prepare(1310.0);
+        comb.set_gain(0.8);
+        comb.set_dampening(0.3);
+        comb.set_modulation(22.0, 0.7);
+        comb
+    });
+    bench_stage(c, "all_pass", || {
+        let mut all_pass = AllPass::new(500);
+        all_pass.prepare(221.0, 0.7);
+        all_pass
+    });
+    bench_stage(c, "mod_all_pass", || {
+        ModAllPass::new(10.0, 2.0, 5.0, 0.5, 0.5, Interpolation::CubicHermite, sample_rate)
+    });
+}
+
+criterion_group!(dsp, bench_stereo_reverb, bench_schroeder, bench_stages);
+criterion_main!(dsp);
@@ -0,0 +1,69 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::block::BlockProcessor;
+use crate::delay_line::{DelayCrossfade, DelayLine};
+use crate::float::Float;
+
+/// Schroeder all-pass with the same interface as the euterpe_rs `AllPass`,
+/// plus `set_delay`, which crossfades to the new length instead of jumping.
+/// Lengths are rounded to whole samples, so the reads are exact and the
+/// filter stays lossless; there is no interpolation to choose.
+pub struct AllPass<T: Float = f64> {
+    delay_line: DelayLine<T>,
+    delay: DelayCrossfade,
+    gain: T,
+}
+
+impl<T: Float> AllPass<T> {
+    pub fn new(max_delay_samples: usize) -> Self {
+        AllPass {
+            delay_line: DelayLine::new(max_delay_samples),
+            delay: DelayCrossfade::new(1.0),
+            gain: T::ZERO,
+        }
+    }
+
+    /// Sets the delay at once, for use before the signal starts.
+    pub fn prepare(&mut self, delay_samples: f64, gain: f64) {
+        self.delay.jump(delay_samples.round());
+        self.gain = T::from_f64(gain);
+    }
+
+    pub fn set_delay(&mut self, delay_samples: f64) {
+        self.delay.set_target(delay_samples.round());
+    }
+
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = T::from_f64(gain);
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for AllPass<T> {
+    fn process(&mut self, input: T) -> T {
+        // Read before writing, so a read of `d - 1` is `d` samples old.
+        let delayed = self.delay.read(|delay, head| self.delay_line.read_head(delay, head), -1.0);
+        let feedback = input + self.gain * delayed;
+        self.delay_line.write(feedback);
+        delayed - self.gain * feedback
+    }
+}
+
+impl<T: Float> BlockProcessor<T> for AllPass<T> {
+    fn process_block_in_place(&mut self, buffer: &mut [T]) {
+        // Without a crossfade the read position is fixed for the whole block.
+        let Some(delay_samples) = self.delay.steady_delay() else {
+            buffer.iter_mut().for_each(|sample| *sample = self.process(*sample));
+            return;
+        };
+        let (gain, head) = (self.gain, self.delay.head());
+        for sample in buffer.iter_mut() {
+            let delayed = self.delay_line.read_head(delay_samples - 1.0, head);
+            let feedback = *sample + gain * delayed;
+            self.delay_line.write(feedback);
+            *sample = delayed - gain * feedback;
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+use euterpe_rs::processor::AudioProcessor;
+use crate::float::Float;
+
+/// Second-order IIR section with the RBJ cookbook designs, run in transposed
+/// direct form II. It belongs in euterpe_rs as `euterpe_rs::biquad`, but that
+/// crate is a submodule maintained upstream, so it lives here until it is
+/// moved over. Written against euterpe_rs' `AudioProcessor` so it can move
+/// as-is. The designs are worked out in `f64`, the coefficients and state
+/// are in the sample type.
+pub struct Biquad<T: Float = f64> {
+    b0: T,
+    b1: T,
+    b2: T,
+    a1: T,
+    a2: T,
+    s1: T,
+    s2: T,
+}
+
+impl<T: Float> Biquad<T> {
+    /// A filter that passes its input unchanged.
+    pub fn new() -> Self {
+        Biquad { b0: T::ONE, b1: T::ZERO, b2: T::ZERO, a1: T::ZERO, a2: T::ZERO, s1: T::ZERO, s2: T::ZERO }
+    }
+
+    /// Butterworth low-pass, -3 dB at `cutoff_hz`.
+    pub fn design_low_pass(&mut self, cutoff_hz: f64, sample_rate: f64) {
+        let (cos, alpha) = Self::prewarp(cutoff_hz, sample_rate, FRAC_1_SQRT_2);
+        self.set_coefficients(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        );
+    }
+
+    /// Butterworth high-pass, -3 dB at `cutoff_hz`.
+    pub fn design_high_pass(&mut self, cutoff_hz: f64, sample_rate: f64) {
+        let (cos, alpha) = Self::prewarp(cutoff_hz, sample_rate, FRAC_1_SQRT_2);
+        self.set_coefficients(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        );
+    }
+
+    /// Shelf with `gain_db` below `corner_hz`, at half the gain at the corner.
+    pub fn design_low_shelf(&mut self, corner_hz: f64, gain_db: f64, sample_rate: f64) {
+        let (cos, alpha) = Self::prewarp(corner_hz, sample_rate, FRAC_1_SQRT_2);
+        let a = 10f64.powf(gain_db / 40.0);
+        let beta = 2.0 * a.sqrt() * alpha;
+        self.set_coefficients(
+            [
+                a * ((a + 1.0) - (a - 1.0) * cos + beta),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - beta),
+            ],
+            [
+                (a + 1.0) + (a - 1.0) * cos + beta,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - beta,
+            ],
+        );
+    }
+
+    /// Shelf with `gain_db` above `corner_hz`, at half the gain at the corner.
+    pub fn design_high_shelf(&mut self, corner_hz: f64, gain_db: f64, sample_rate: f64) {
+        let (cos, alpha) = Self::prewarp(corner_hz, sample_rate, FRAC_1_SQRT_2);
+        let a = 10f64.powf(gain_db / 40.0);
+        let beta = 2.0 * a.sqrt() * alpha;
+        self.set_coefficients(
+            [
+                a * ((a + 1.0) + (a - 1.0) * cos + beta),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - beta),
+            ],
+            [
+                (a + 1.0) - (a - 1.0) * cos + beta,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - beta,
+            ],
+        );
+    }
+
+    pub fn reset(&mut self) {
+        self.s1 = T::ZERO;
+        self.s2 = T::ZERO;
+    }
+
+    /// Returns cos(w0) and alpha for a design at `freq_hz`, kept below Nyquist.
+    fn prewarp(freq_hz: f64, sample_rate: f64, q: f64) -> (f64, f64) {
+        let omega = 2.0 * PI * freq_hz.min(0.49 * sample_rate) / sample_rate;
+        (omega.cos(), omega.sin() / (2.0 * q))
+    }
+
+    fn set_coefficients(&mut self, b: [f64; 3], a: [f64; 3]) {
+        self.b0 = T::from_f64(b[0] / a[0]);
+        self.b1 = T::from_f64(b[1] / a[0]);
+        self.b2 = T::from_f64(b[2] / a[0]);
+        self.a1 = T::from_f64(a[1] / a[0]);
+        self.a2 = T::from_f64(a[2] / a[0]);
+    }
+}
+
+impl<T: Float> Default for Biquad<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for Biquad<T> {
+    fn process(&mut self, input: T) -> T {
+        let output = self.b0 * input + self.s1;
+        self.s1 = self.b1 * input - self.a1 * output + self.s2;
+        self.s2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    /// Gain in dB of `filter`, from its coefficients.
+    fn magnitude_db(filter: &Biquad, freq_hz: f64) -> f64 {
+        let omega = 2.0 * PI * freq_hz / SAMPLE_RATE;
+        let (cos1, sin1, cos2, sin2) = (omega.cos(), omega.sin(), (2.0 * omega).cos(), (2.0 * omega).sin());
+        let num = (filter.b0 + filter.b1 * cos1 + filter.b2 * cos2, -filter.b1 * sin1 - filter.b2 * sin2);
+        let den = (1.0 + filter.a1 * cos1 + filter.a2 * cos2, -filter.a1 * sin1 - filter.a2 * sin2);
+        10.0 * ((num.0 * num.0 + num.1 * num.1) / (den.0 * den.0 + den.1 * den.1)).log10()
+    }
+
+    /// Gain in dB of `filter` on a steady sine at `freq_hz`, from the output
+    /// power over one second. Whole cycles for whole-hertz frequencies.
+    fn measured_db(filter: &mut Biquad, freq_hz: f64) -> f64 {
+        let sine = |n: usize| (2.0 * PI * freq_hz * n as f64 / SAMPLE_RATE).sin();
+        let one_second = SAMPLE_RATE as usize;
+        let power = (0..2 * one_second)
+            .map(|n| filter.process(sine(n)).powi(2))
+            .skip(one_second)
+            .sum::<f64>()
+            / one_second as f64;
+        10.0 * (power / 0.5).log10()
+    }
+
+    fn assert_db(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} dB vs {} dB", actual, expected);
+    }
+
+    #[test]
+    fn test_high_pass_response() {
+        let mut uut = Biquad::new();
+        uut.design_high_pass(200.0, SAMPLE_RATE);
+        assert_db(magnitude_db(&uut, 200.0), -3.01, 0.01);
+        assert_db(magnitude_db(&uut, 20.0), -40.0, 0.1);
+        assert_db(magnitude_db(&uut, 5000.0), 0.0, 0.01);
+        assert_db(measured_db(&mut uut, 200.0), -3.01, 0.05);
+    }
+
+    #[test]
+    fn test_low_pass_response() {
+        let mut uut = Biquad::new();
+        uut.design_low_pass(2000.0, SAMPLE_RATE);
+        assert_db(magnitude_db(&uut, 2000.0), -3.01, 0.01);
+        assert_db(magnitude_db(&uut, 20.0), 0.0, 0.01);
+        // Octaves above the cutoff fall at 12 dB each.
+        let octave_slope = magnitude_db(&uut, 8000.0) - magnitude_db(&uut, 4000.0);
+        assert_db(octave_slope, -12.0, 1.5);
+        assert_db(measured_db(&mut uut, 4000.0), magnitude_db(&uut, 4000.0), 0.05);
+    }
+
+    #[test]
+    fn test_shelf_response() {
+        let mut low = Biquad::new();
+        low.design_low_shelf(1000.0, -6.0, SAMPLE_RATE);
+        let mut high = Biquad::new();
+        high.design_high_shelf(1000.0, 6.0, SAMPLE_RATE);
+
+        assert_db(magnitude_db(&low, 10.0), -6.0, 0.05);
+        assert_db(magnitude_db(&low, 1000.0), -3.0, 0.01);
+        assert_db(magnitude_db(&low, 20000.0), 0.0, 0.05);
+        assert_db(magnitude_db(&high, 10.0), 0.0, 0.05);
+        assert_db(magnitude_db(&high, 1000.0), 3.0, 0.01);
+        assert_db(magnitude_db(&high, 20000.0), 6.0, 0.1);
+    }
+}
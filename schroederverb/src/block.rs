@@ -0,0 +1,24 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::float::Float;
+
+/// Longest run of samples the stage-by-stage loops handle at once. Longer
+/// blocks are split, so their scratch buffers can live on the stack.
+pub const MAX_BLOCK_SIZE: usize = 64;
+
+/// Block-based companion to the euterpe_rs `AudioProcessor`, which only
+/// takes one sample at a time. The defaults fall back to `process`;
+/// processors that can keep their state in registers across a block, or run
+/// a whole block through one stage before the next, override
+/// `process_block_in_place`.
+pub trait BlockProcessor<T: Float>: AudioProcessor<T> {
+    fn process_block(&mut self, input: &[T], output: &mut [T]) {
+        output.copy_from_slice(input);
+        self.process_block_in_place(output);
+    }
+
+    fn process_block_in_place(&mut self, buffer: &mut [T]) {
+        for sample in buffer.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
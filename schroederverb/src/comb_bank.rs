@@ -0,0 +1,335 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::decay_filter::BandDecayFilter;
+use crate::delay_line::{DelayCrossfade, Interpolation};
+use crate::float::{Float, LANES};
+use crate::lfo::wrap;
+
+/// `N` low-pass feedback combs fed the same input and run in lock-step. Lane
+/// by lane the output is identical to `N` separate `ModComb`s, but the
+/// interpolation and low-pass run `LANES` lanes at a time. `N` must be a
+/// multiple of `LANES`.
+pub struct CombBank<T: Float, const N: usize> {
+    /// Every lane's delay line, one after the other. Interleaving them would
+    /// put each lane's reads, which are all at different delays, on a new
+    /// cache line every few samples.
+    buffer: Vec<T>,
+    /// Length of each lane's line.
+    length: usize,
+    write_index: usize,
+    max_delay_samples: f64,
+    interpolation: Interpolation,
+    /// Each lane's all-pass interpolator state for read heads 0 and 1, see
+    /// `DelayLine::read_head`.
+    all_pass_states: [[T; 2]; N],
+    delays: [DelayCrossfade; N],
+    /// Triangle LFOs, as in `ModComb`, kept as phases so they can run
+    /// together.
+    lfo_phases: [f64; N],
+    lfo_increments: [f64; N],
+    sample_rate: f64,
+    depth_samples: [f64; N],
+    filters: [BandDecayFilter<T>; N],
+    dampening: [T; N],
+    filter_stores: [T; N],
+}
+
+impl<T: Float, const N: usize> CombBank<T, N> {
+    pub fn new(max_delay_samples: usize, interpolation: Interpolation, sample_rate: f64) -> Self {
+        assert!(N.is_multiple_of(LANES), "a comb bank needs a multiple of {} lanes", LANES);
+        CombBank {
+            // Same guard samples as `DelayLine`.
+            buffer: vec![T::ZERO; N * (max_delay_samples + 4)],
+            length: max_delay_samples + 4,
+            write_index: 0,
+            max_delay_samples: max_delay_samples as f64,
+            interpolation,
+            all_pass_states: [[T::ZERO; 2]; N],
+            delays: std::array::from_fn(|_| DelayCrossfade::new(1.0)),
+            lfo_phases: [0.0; N],
+            lfo_increments: [0.0; N],
+            sample_rate,
+            depth_samples: [0.0; N],
+            filters: std::array::from_fn(|_| {
+                let mut filter = BandDecayFilter::new();
+                filter.set_gain(0.0);
+                filter
+            }),
+            dampening: [T::ZERO; N],
+            filter_stores: [T::ZERO; N],
+        }
+    }
+
+    /// Sets the delay of `lane` at once, for use before the signal starts.
+    pub fn prepare(&mut self, lane: usize, delay_samples: f64) {
+        self.delays[lane].jump(delay_samples.round());
+    }
+
+    /// Crossfades `lane` to a new delay, see `ModComb::set_delay`.
+    pub fn set_delay(&mut self, lane: usize, delay_samples: f64) {
+        self.delays[lane].set_target(delay_samples.round());
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|sample| *sample = T::ZERO);
+        self.all_pass_states = [[T::ZERO; 2]; N];
+        self.filter_stores = [T::ZERO; N];
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+    }
+
+    pub fn set_gain(&mut self, lane: usize, gain: f64) {
+        self.filters[lane].set_gain(gain);
+    }
+
+    /// Loop gains of `lane` in the low, mid and high bands, see
+    /// `BandDecayFilter::design`.
+    pub fn set_band_gains(&mut self, lane: usize, gains: (f64, f64, f64), crossovers_hz: (f64, f64)) {
+        self.filters[lane].design(gains, crossovers_hz, self.sample_rate);
+    }
+
+    pub fn set_dampening(&mut self, lane: usize, dampening: f64) {
+        self.dampening[lane] = T::from_f64(dampening);
+    }
+
+    pub fn set_modulation(&mut self, lane: usize, depth_samples: f64, rate_hz: f64) {
+        self.depth_samples[lane] = depth_samples;
+        self.lfo_increments[lane] = rate_hz / self.sample_rate;
+    }
+
+    pub fn set_lfo_phase(&mut self, lane: usize, phase: f64) {
+        self.lfo_phases[lane] = phase.rem_euclid(1.0);
+    }
+
+    /// Feeds `input` to every comb and returns each one's output.
+    pub fn process(&mut self, input: T) -> [T; N] {
+        // Read before writing, so a read of `d - 1` is `d` samples old.
+        let steady = self.delays.iter().all(|delay| delay.steady_delay().is_some());
+        let outputs = if steady && self.depth_samples.iter().all(|depth| *depth == 0.0) {
+            // Unmodulated, every read is a whole sample.
+            self.advance_lfos();
+            std::array::from_fn(|lane| {
+                let delay = (self.delays[lane].steady_delay().unwrap() - 1.0).clamp(0.0, self.max_delay_samples);
+                self.line(lane)[self.index_behind(delay as usize)]
+            })
+        } else if steady {
+            let modulations = self.next_modulations();
+            let delays = std::array::from_fn(|lane| self.delays[lane].steady_delay().unwrap() + (modulations[lane] - 1.0));
+            self.read_lanes(&delays)
+        } else {
+            let modulations = self.next_modulations();
+            self.read_crossfading(&modulations)
+        };
+
+        for lane in (0..N).step_by(LANES) {
+            let output = T::load(&outputs[lane..]);
+            let dampening = T::load(&self.dampening[lane..]);
+            let filter_store = output * (T::splat(T::ONE) - dampening) + T::load(&self.filter_stores[lane..]) * dampening;
+            T::store(filter_store, &mut self.filter_stores[lane..]);
+        }
+        self.write_index = if self.write_index + 1 == self.length { 0 } else { self.write_index + 1 };
+        for lane in 0..N {
+            let feedback = self.filters[lane].process(self.filter_stores[lane]);
+            self.buffer[lane * self.length + self.write_index] = input + feedback;
+        }
+        outputs
+    }
+
+    fn line(&self, lane: usize) -> &[T] {
+        &self.buffer[lane * self.length..(lane + 1) * self.length]
+    }
+
+    /// Index in a line of the sample `delay` behind the newest one.
+    fn index_behind(&self, delay: usize) -> usize {
+        // `delay` never passes the guard samples, so one wrap is enough.
+        let index = self.write_index + self.length - delay;
+        if index >= self.length { index - self.length } else { index }
+    }
+
+    /// Each lane's LFO times its depth, advancing the LFOs by one sample. The
+    /// same arithmetic as `Lfo::next` on a triangle.
+    fn next_modulations(&mut self) -> [f64; N] {
+        let modulations = std::array::from_fn(|lane| {
+            self.depth_samples[lane] * (1.0 - 4.0 * (wrap(self.lfo_phases[lane] + 0.25) - 0.5).abs())
+        });
+        self.advance_lfos();
+        modulations
+    }
+
+    fn advance_lfos(&mut self) {
+        for (phase, increment) in self.lfo_phases.iter_mut().zip(self.lfo_increments.iter()) {
+            *phase = wrap(*phase + *increment);
+        }
+    }
+
+    /// Reads each lane at its delay, working out the positions lane by lane
+    /// and interpolating `LANES` lanes at a time. Does what
+    /// `Interpolation::read` does, in the same order, so the results match it
+    /// exactly.
+    fn read_lanes(&mut self, delays: &[f64; N]) -> [T; N] {
+        let length = self.length;
+        // The indices of the samples one older and one newer than `index`.
+        let older = |index: usize| if index == 0 { length - 1 } else { index - 1 };
+        let newer = |index: usize| if index + 1 == length { 0 } else { index + 1 };
+        let (mut fracs, mut wholes, mut indices, mut current) = ([0.0; N], [0; N], [0; N], [T::ZERO; N]);
+        for lane in 0..N {
+            let delay = delays[lane].clamp(0.0, self.max_delay_samples);
+            let (mut whole, mut frac) = (delay as usize, delay - (delay as usize) as f64);
+            if self.interpolation == Interpolation::AllPass && frac != 0.0 && frac < 0.5 && whole > 0 {
+                (whole, frac) = (whole - 1, frac + 1.0);
+            }
+            (fracs[lane], wholes[lane], indices[lane]) = (frac, whole, self.index_behind(whole));
+            current[lane] = self.line(lane)[indices[lane]];
+        }
+        if fracs.iter().all(|frac| *frac == 0.0) {
+            return current;
+        }
+
+        let (mut before, mut next, mut after) = ([T::ZERO; N], [T::ZERO; N], [T::ZERO; N]);
+        for lane in 0..N {
+            let (line, index) = (self.line(lane), indices[lane]);
+            // At a delay of 0 there is no newer sample, so the newest one
+            // repeats.
+            before[lane] = line[if wholes[lane] == 0 { index } else { newer(index) }];
+            next[lane] = line[older(index)];
+            after[lane] = line[older(older(index))];
+        }
+
+        let mut outputs = [T::ZERO; N];
+        let coefficients = |coefficient: fn(f64) -> f64| fracs.map(|frac| T::from_f64(coefficient(frac)));
+        match self.interpolation {
+            Interpolation::Linear => {
+                let (c0, c1) = (coefficients(|frac| 1.0 - frac), coefficients(|frac| frac));
+                for lane in (0..N).step_by(LANES) {
+                    let out = T::load(&current[lane..]) * T::load(&c0[lane..]) + T::load(&next[lane..]) * T::load(&c1[lane..]);
+                    T::store(out, &mut outputs[lane..]);
+                }
+            }
+            Interpolation::CubicHermite => {
+                let fracs = coefficients(|frac| frac);
+                let (half, two_and_half, two, one_and_half) =
+                    (T::splat(T::from_f64(0.5)), T::splat(T::from_f64(2.5)), T::splat(T::from_f64(2.0)), T::splat(T::from_f64(1.5)));
+                for lane in (0..N).step_by(LANES) {
+                    let (before, current, next, after) =
+                        (T::load(&before[lane..]), T::load(&current[lane..]), T::load(&next[lane..]), T::load(&after[lane..]));
+                    let c1 = half * (next - before);
+                    let c2 = before - two_and_half * current + two * next - half * after;
+                    let c3 = half * (after - before) + one_and_half * (current - next);
+                    let frac = T::load(&fracs[lane..]);
+                    T::store(((c3 * frac + c2) * frac + c1) * frac + current, &mut outputs[lane..]);
+                }
+            }
+            Interpolation::Lagrange => {
+                let c0 = coefficients(|frac| -frac * (frac - 1.0) * (frac - 2.0) / 6.0);
+                let c1 = coefficients(|frac| (frac + 1.0) * (frac - 1.0) * (frac - 2.0) / 2.0);
+                let c2 = coefficients(|frac| (frac + 1.0) * frac * (frac - 2.0) / 2.0);
+                let c3 = coefficients(|frac| (frac + 1.0) * frac * (frac - 1.0) / 6.0);
+                for lane in (0..N).step_by(LANES) {
+                    let out = T::load(&c0[lane..]) * T::load(&before[lane..]) + T::load(&c1[lane..]) * T::load(&current[lane..])
+                        - T::load(&c2[lane..]) * T::load(&next[lane..])
+                        + T::load(&c3[lane..]) * T::load(&after[lane..]);
+                    T::store(out, &mut outputs[lane..]);
+                }
+            }
+            Interpolation::AllPass => {
+                let c = coefficients(|frac| (1.0 - frac) / (1.0 + frac));
+                let heads = self.delays.each_ref().map(DelayCrossfade::head);
+                let states: [T; N] = std::array::from_fn(|lane| self.all_pass_states[lane][heads[lane]]);
+                for lane in (0..N).step_by(LANES) {
+                    let state = T::load(&c[lane..]) * (T::load(&current[lane..]) - T::load(&states[lane..])) + T::load(&next[lane..]);
+                    T::store(state, &mut outputs[lane..]);
+                }
+                // Whole-sample reads leave the state alone.
+                for lane in 0..N {
+                    if fracs[lane] != 0.0 {
+                        self.all_pass_states[lane][heads[lane]] = outputs[lane];
+                    }
+                }
+            }
+        }
+        // Whole-sample reads are exact in every mode.
+        for lane in 0..N {
+            if fracs[lane] == 0.0 {
+                outputs[lane] = current[lane];
+            }
+        }
+        outputs
+    }
+
+    /// Reads each lane on its own through its crossfade, for the rare samples
+    /// where a delay is changing.
+    fn read_crossfading(&mut self, modulations: &[f64; N]) -> [T; N] {
+        let (interpolation, max_delay_samples) = (self.interpolation, self.max_delay_samples);
+        std::array::from_fn(|lane| {
+            let (line, write_index) = (&self.buffer[lane * self.length..(lane + 1) * self.length], self.write_index);
+            let all_pass_states = &mut self.all_pass_states[lane];
+            let sample = |delay: usize| line[(write_index + line.len() - delay) % line.len()];
+            self.delays[lane].read(
+                |delay, head| interpolation.read(delay.clamp(0.0, max_delay_samples), sample, &mut all_pass_states[head]),
+                modulations[lane] - 1.0,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_comb::ModComb;
+    use crate::test_util::noise;
+
+    fn matches_mod_combs<T: Float>(interpolation: Interpolation) {
+        const NUM_COMBS: usize = 8;
+        let sample_rate = 44100.0;
+        let mut uut = CombBank::<T, NUM_COMBS>::new(400, interpolation, sample_rate);
+        let mut reference: [ModComb<T>; NUM_COMBS] =
+            std::array::from_fn(|_| ModComb::new(400, interpolation, sample_rate));
+        for (lane, reference) in reference.iter_mut().enumerate() {
+            let (delay_samples, gain, dampening) = (100.0 + 37.0 * lane as f64, 0.9 - 0.01 * lane as f64, 0.1 * lane as f64);
+            let (depth_samples, rate_hz) = (0.5 * lane as f64, 0.3 + lane as f64);
+            // Distinct band gains, so the loop filters are in play.
+            let gains = (gain.powf(0.8), gain, gain.powf(1.5));
+            uut.prepare(lane, delay_samples);
+            uut.set_band_gains(lane, gains, (200.0, 4000.0));
+            uut.set_dampening(lane, dampening);
+            uut.set_modulation(lane, depth_samples, rate_hz);
+            uut.set_lfo_phase(lane, lane as f64 / NUM_COMBS as f64);
+            reference.prepare(delay_samples);
+            reference.set_band_gains(gains, (200.0, 4000.0));
+            reference.set_dampening(dampening);
+            reference.set_modulation(depth_samples, rate_hz);
+            reference.set_lfo_phase(lane as f64 / NUM_COMBS as f64);
+        }
+
+        let mut seed: u32 = 1;
+        for n in 0..20000 {
+            // A length change part way through, to cover the crossfades.
+            if n == 5000 {
+                for (lane, reference) in reference.iter_mut().enumerate() {
+                    uut.set_delay(lane, 150.0 + 29.0 * lane as f64);
+                    reference.set_delay(150.0 + 29.0 * lane as f64);
+                }
+            }
+            // A stretch with the modulation off, after which the LFOs must
+            // still be in step.
+            if n == 10000 || n == 15000 {
+                for (lane, reference) in reference.iter_mut().enumerate() {
+                    let (depth_samples, rate_hz) = (if n == 10000 { 0.0 } else { 2.0 }, 0.3 + lane as f64);
+                    uut.set_modulation(lane, depth_samples, rate_hz);
+                    reference.set_modulation(depth_samples, rate_hz);
+                }
+            }
+            let input = noise::<T>(&mut seed);
+            let expected: [T; NUM_COMBS] = std::array::from_fn(|lane| reference[lane].process(input));
+            assert_eq!(uut.process(input), expected, "{:?} sample {}", interpolation, n);
+        }
+    }
+
+    #[test]
+    fn test_matches_mod_combs() {
+        for interpolation in [Interpolation::Linear, Interpolation::CubicHermite, Interpolation::Lagrange, Interpolation::AllPass] {
+            matches_mod_combs::<f32>(interpolation);
+            matches_mod_combs::<f64>(interpolation);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::f64::consts::PI;
+use euterpe_rs::processor::AudioProcessor;
+use crate::float::Float;
+
+/// First-order shelving section with independent gains at DC and Nyquist,
+/// designed with the bilinear transform around `crossover_hz`.
+struct Shelf<T: Float> {
+    b0: T,
+    b1: T,
+    a1: T,
+    x1: T,
+    y1: T,
+}
+
+impl<T: Float> Shelf<T> {
+    fn new() -> Self {
+        Shelf { b0: T::ONE, b1: T::ZERO, a1: T::ZERO, x1: T::ZERO, y1: T::ZERO }
+    }
+
+    fn design(&mut self, dc_gain: f64, nyquist_gain: f64, crossover_hz: f64, sample_rate: f64) {
+        // A flat shelf is kept an exact gain, so equal band gains leave a
+        // loop exactly as a plain gain would.
+        if dc_gain == nyquist_gain {
+            self.set_gain(dc_gain);
+            return;
+        }
+        let k = (PI * crossover_hz / sample_rate).tan();
+        let norm = 1.0 / (1.0 + k);
+        self.b0 = T::from_f64((nyquist_gain + dc_gain * k) * norm);
+        self.b1 = T::from_f64((dc_gain * k - nyquist_gain) * norm);
+        self.a1 = T::from_f64((k - 1.0) * norm);
+    }
+
+    fn set_gain(&mut self, gain: f64) {
+        self.b0 = T::from_f64(gain);
+        self.b1 = T::ZERO;
+        self.a1 = T::ZERO;
+    }
+
+    fn process(&mut self, input: T) -> T {
+        let output = self.b0 * input + self.b1 * self.x1 - self.a1 * self.y1;
+        self.x1 = input;
+        self.y1 = output;
+        output
+    }
+}
+
+/// Loop filter with separate gains for the low, mid and high bands: a low
+/// shelf and a high shelf around the mid-band gain. Placed in a feedback
+/// loop, each band decays at the rate its gain sets.
+pub struct BandDecayFilter<T: Float = f64> {
+    low_shelf: Shelf<T>,
+    high_shelf: Shelf<T>,
+    mid_gain: T,
+}
+
+impl<T: Float> BandDecayFilter<T> {
+    pub fn new() -> Self {
+        BandDecayFilter {
+            low_shelf: Shelf::new(),
+            high_shelf: Shelf::new(),
+            mid_gain: T::ONE,
+        }
+    }
+
+    /// `gains` are the (low, mid, high) loop gains. The high gain must not
+    /// exceed the mid gain, or the shelves can overshoot 1 where they overlap.
+    pub fn design(&mut self, gains: (f64, f64, f64), crossovers_hz: (f64, f64), sample_rate: f64) {
+        let (low_gain, mid_gain, high_gain) = gains;
+        // Only an RT60 of zero gets here, which silences every band.
+        if mid_gain == 0.0 {
+            self.set_gain(0.0);
+            return;
+        }
+        self.mid_gain = T::from_f64(mid_gain);
+        self.low_shelf.design(low_gain / mid_gain, 1.0, crossovers_hz.0, sample_rate);
+        self.high_shelf.design(1.0, high_gain / mid_gain, crossovers_hz.1, sample_rate);
+    }
+
+    /// The same `gain` in every band.
+    pub fn set_gain(&mut self, gain: f64) {
+        self.mid_gain = T::from_f64(gain);
+        self.low_shelf.set_gain(1.0);
+        self.high_shelf.set_gain(1.0);
+    }
+
+    pub fn reset(&mut self) {
+        for shelf in [&mut self.low_shelf, &mut self.high_shelf] {
+            shelf.x1 = T::ZERO;
+            shelf.y1 = T::ZERO;
+        }
+    }
+}
+
+impl<T: Float> Default for BandDecayFilter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for BandDecayFilter<T> {
+    fn process(&mut self, input: T) -> T {
+        self.high_shelf.process(self.low_shelf.process(input * self.mid_gain))
+    }
+}
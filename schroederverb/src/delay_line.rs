@@ -0,0 +1,341 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::block::BlockProcessor;
+use crate::float::Float;
+
+/// How a `DelayLine` reads between two samples. Whole-sample delays read
+/// exactly in every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Cheapest; rolls off the highs at fractional delays.
+    Linear,
+    /// 4-point, 3rd-order Hermite (Catmull-Rom) spline.
+    CubicHermite,
+    /// 4-point, 3rd-order Lagrange polynomial.
+    Lagrange,
+    /// 1st-order all-pass: flat magnitude, but it keeps state between reads,
+    /// so each read head must be read once per sample. Whole-sample reads
+    /// leave the state alone.
+    AllPass,
+}
+
+/// Plain circular delay line with fractional, interpolated reads.
+pub struct DelayLine<T: Float = f64> {
+    buffer: Vec<T>,
+    write_index: usize,
+    delay_samples: f64,
+    max_delay_samples: f64,
+    interpolation: Interpolation,
+    /// Last output of the all-pass interpolator, for each read head.
+    all_pass_states: [T; 2],
+}
+
+impl<T: Float> DelayLine<T> {
+    pub fn new(max_delay_samples: usize) -> Self {
+        DelayLine::with_interpolation(max_delay_samples, Interpolation::Linear)
+    }
+
+    pub fn with_interpolation(max_delay_samples: usize, interpolation: Interpolation) -> Self {
+        DelayLine {
+            // Room for the samples either side of the longest delay that the
+            // 4-point interpolators need.
+            buffer: vec![T::ZERO; max_delay_samples + 4],
+            write_index: 0,
+            delay_samples: 0.0,
+            max_delay_samples: max_delay_samples as f64,
+            interpolation,
+            all_pass_states: [T::ZERO; 2],
+        }
+    }
+
+    pub fn set_delay(&mut self, delay_samples: f64) {
+        self.delay_samples = delay_samples;
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|sample| *sample = T::ZERO);
+        self.all_pass_states = [T::ZERO; 2];
+    }
+
+    /// Reads the line at the delay set with `set_delay`.
+    pub fn tap(&mut self) -> T {
+        self.read(self.delay_samples)
+    }
+
+    pub fn write(&mut self, input: T) {
+        self.write_index = (self.write_index + 1) % self.buffer.len();
+        self.buffer[self.write_index] = input;
+    }
+
+    /// Reads the line `delay_samples` behind the last written sample. The delay
+    /// is clamped to the length the line was built for.
+    pub fn read(&mut self, delay_samples: f64) -> T {
+        self.read_head(delay_samples, 0)
+    }
+
+    /// `read` through read head 0 or 1. The heads only differ in all-pass
+    /// mode, where each keeps its own interpolator state, so a crossfade can
+    /// read two positions every sample.
+    pub fn read_head(&mut self, delay_samples: f64, head: usize) -> T {
+        let delay = delay_samples.clamp(0.0, self.max_delay_samples);
+        let (buffer, write_index) = (&self.buffer, self.write_index);
+        let sample = |delay: usize| buffer[(write_index + buffer.len() - delay) % buffer.len()];
+        self.interpolation.read(delay, sample, &mut self.all_pass_states[head])
+    }
+}
+
+impl Interpolation {
+    /// Reads `delay_samples` behind the newest sample of a line, where
+    /// `sample(n)` is the sample `n` behind the newest one. Lines with another
+    /// layout than `DelayLine` share the kernels through this.
+    pub(crate) fn read<T: Float>(self, delay_samples: f64, sample: impl Fn(usize) -> T, all_pass_state: &mut T) -> T {
+        // Truncates, as `floor` does for the non-negative delays a line takes,
+        // without the libm call `floor` is on targets before SSE4.1.
+        let whole = delay_samples as usize;
+        let frac = delay_samples - whole as f64;
+        if frac == 0.0 {
+            return sample(whole);
+        }
+        // The samples one newer than, at, one older and two older than
+        // `whole`. At a delay of 0 there is no newer sample, so the newest one
+        // repeats.
+        let four_samples = || (sample(whole.saturating_sub(1)), sample(whole), sample(whole + 1), sample(whole + 2));
+
+        match self {
+            Interpolation::Linear => sample(whole) * T::from_f64(1.0 - frac) + sample(whole + 1) * T::from_f64(frac),
+            Interpolation::CubicHermite => {
+                let (before, current, next, after) = four_samples();
+                let half = T::from_f64(0.5);
+                let c1 = half * (next - before);
+                let c2 = before - T::from_f64(2.5) * current + T::from_f64(2.0) * next - half * after;
+                let c3 = half * (after - before) + T::from_f64(1.5) * (current - next);
+                let frac = T::from_f64(frac);
+                ((c3 * frac + c2) * frac + c1) * frac + current
+            }
+            Interpolation::Lagrange => {
+                let (before, current, next, after) = four_samples();
+                T::from_f64(-frac * (frac - 1.0) * (frac - 2.0) / 6.0) * before
+                    + T::from_f64((frac + 1.0) * (frac - 1.0) * (frac - 2.0) / 2.0) * current
+                    - T::from_f64((frac + 1.0) * frac * (frac - 2.0) / 2.0) * next
+                    + T::from_f64((frac + 1.0) * frac * (frac - 1.0) / 6.0) * after
+            }
+            Interpolation::AllPass => {
+                // Keeps the fraction in [0.5, 1.5), well away from the pole
+                // at -1 that a fraction near 0 would put the filter on.
+                let (whole, frac) = if frac < 0.5 && whole > 0 { (whole - 1, frac + 1.0) } else { (whole, frac) };
+                let coefficient = T::from_f64((1.0 - frac) / (1.0 + frac));
+                *all_pass_state = coefficient * (sample(whole) - *all_pass_state) + sample(whole + 1);
+                *all_pass_state
+            }
+        }
+    }
+}
+
+/// Length of the crossfade between the old and new read positions when a
+/// delay changes.
+const DELAY_CROSSFADE_SAMPLES: f64 = 1024.0;
+
+/// Read position of a delay line that moves to a new delay by crossfading
+/// from the old tap to the new one, so a length change neither clicks nor
+/// shifts the pitch. A change requested mid-fade starts once it is done.
+pub(crate) struct DelayCrossfade {
+    delay_samples: f64,
+    next_delay_samples: f64,
+    target_delay_samples: f64,
+    position: f64,
+    /// Read head at `delay_samples`. The other one reads the delay being
+    /// faded to and takes over when the fade ends.
+    head: usize,
+}
+
+impl DelayCrossfade {
+    pub fn new(delay_samples: f64) -> Self {
+        DelayCrossfade {
+            delay_samples,
+            next_delay_samples: delay_samples,
+            target_delay_samples: delay_samples,
+            position: 0.0,
+            head: 0,
+        }
+    }
+
+    /// Moves to `delay_samples` at once.
+    pub fn jump(&mut self, delay_samples: f64) {
+        *self = DelayCrossfade::new(delay_samples);
+    }
+
+    pub fn set_target(&mut self, delay_samples: f64) {
+        self.target_delay_samples = delay_samples;
+    }
+
+    /// Moves to the target at once, for when the line has been cleared and
+    /// there is nothing to fade.
+    pub fn settle(&mut self) {
+        self.jump(self.target_delay_samples);
+    }
+
+    /// The delay, when it is settled and no crossfade is running or due.
+    pub fn steady_delay(&self) -> Option<f64> {
+        (self.position == 0.0 && self.target_delay_samples == self.delay_samples).then_some(self.delay_samples)
+    }
+
+    /// Read head to use for reads at `steady_delay`, see `DelayLine::read_head`.
+    pub fn head(&self) -> usize {
+        self.head
+    }
+
+    /// Reads at the current delay plus `offset_samples` through `read`, which
+    /// reads the line at a given delay through a given read head, and advances
+    /// the crossfade by one sample.
+    pub fn read<T: Float>(&mut self, mut read: impl FnMut(f64, usize) -> T, offset_samples: f64) -> T {
+        if self.position == 0.0 {
+            if self.target_delay_samples == self.delay_samples {
+                return read(self.delay_samples + offset_samples, self.head);
+            }
+            self.next_delay_samples = self.target_delay_samples;
+        }
+
+        self.position += 1.0 / DELAY_CROSSFADE_SAMPLES;
+        let out = read(self.delay_samples + offset_samples, self.head) * T::from_f64(1.0 - self.position)
+            + read(self.next_delay_samples + offset_samples, 1 - self.head) * T::from_f64(self.position);
+        if self.position >= 1.0 {
+            self.delay_samples = self.next_delay_samples;
+            self.position = 0.0;
+            self.head = 1 - self.head;
+        }
+        out
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for DelayLine<T> {
+    fn process(&mut self, input: T) -> T {
+        self.write(input);
+        self.tap()
+    }
+}
+
+impl<T: Float> BlockProcessor<T> for DelayLine<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::noise;
+
+    fn delayed_impulse<T: Float>() {
+        let mut uut = DelayLine::<T>::new(100);
+        uut.set_delay(10.5);
+        let out: Vec<f64> = (0..20).map(|n| uut.process(if n == 0 { T::ONE } else { T::ZERO }).to_f64()).collect();
+        assert_eq!(out[10], 0.5);
+        assert_eq!(out[11], 0.5);
+        assert_eq!(out.iter().sum::<f64>(), 1.0);
+
+        uut.set_delay(1000.0);
+        uut.process(T::ONE);
+        assert_eq!(uut.read(1000.0), uut.read(100.0));
+    }
+
+    #[test]
+    fn test_delayed_impulse() {
+        delayed_impulse::<f32>();
+        delayed_impulse::<f64>();
+    }
+
+    fn all_pass_crossfade_matches_two_lines<T: Float>() {
+        // The two positions of a fade must each run their own interpolator,
+        // as if they were read from separate lines.
+        let (old_delay, new_delay) = (10.5, 20.25);
+        let mut uut = DelayLine::<T>::with_interpolation(100, Interpolation::AllPass);
+        let mut crossfade = DelayCrossfade::new(old_delay);
+        let mut old_line = DelayLine::<T>::with_interpolation(100, Interpolation::AllPass);
+        let mut new_line = DelayLine::<T>::with_interpolation(100, Interpolation::AllPass);
+
+        let fade_start = 200;
+        let fade_end = fade_start + DELAY_CROSSFADE_SAMPLES as usize;
+        let mut seed: u32 = 1;
+        for n in 0..fade_end + 200 {
+            if n == fade_start {
+                crossfade.set_target(new_delay);
+            }
+            let input = noise::<T>(&mut seed);
+            for line in [&mut uut, &mut old_line, &mut new_line] {
+                line.write(input);
+            }
+
+            let expected = if n < fade_start {
+                old_line.read(old_delay)
+            } else if n < fade_end {
+                let position = (n + 1 - fade_start) as f64 / DELAY_CROSSFADE_SAMPLES;
+                old_line.read(old_delay) * T::from_f64(1.0 - position) + new_line.read(new_delay) * T::from_f64(position)
+            } else {
+                new_line.read(new_delay)
+            };
+            assert_eq!(crossfade.read(|delay, head| uut.read_head(delay, head), 0.0), expected, "sample {}", n);
+        }
+    }
+
+    #[test]
+    fn test_all_pass_crossfade_matches_two_lines() {
+        all_pass_crossfade_matches_two_lines::<f32>();
+        all_pass_crossfade_matches_two_lines::<f64>();
+    }
+
+    /// Gain and delay error in samples of a `delay_samples` read of a sine
+    /// running `cycles` times in 1000 samples, by correlating the steady-state
+    /// output against the input.
+    fn measure<T: Float>(interpolation: Interpolation, delay_samples: f64, cycles: usize) -> (f64, f64) {
+        let num_samples = 1000;
+        let omega = 2.0 * std::f64::consts::PI * cycles as f64 / num_samples as f64;
+        let mut uut = DelayLine::<T>::with_interpolation(100, interpolation);
+        uut.set_delay(delay_samples);
+        for n in 0..num_samples {
+            uut.process(T::from_f64((omega * n as f64).sin()));
+        }
+
+        let (mut in_phase, mut quadrature) = (0.0, 0.0);
+        for n in num_samples..2 * num_samples {
+            let out = uut.process(T::from_f64((omega * n as f64).sin())).to_f64();
+            in_phase += out * (omega * n as f64).sin();
+            quadrature += out * (omega * n as f64).cos();
+        }
+        let gain = 2.0 * in_phase.hypot(quadrature) / num_samples as f64;
+        // The phase only gives the delay modulo one period.
+        let period = num_samples as f64 / cycles as f64;
+        let delay_error = ((-quadrature).atan2(in_phase) / omega - delay_samples).rem_euclid(period);
+        (gain, if delay_error > period / 2.0 { delay_error - period } else { delay_error })
+    }
+
+    /// `precision` is how close an exact read of `T` comes to the ideal.
+    fn interpolation_frequency_response<T: Float>(precision: f64) {
+        // Lowest gain and largest delay error at a tenth of the sample rate.
+        let limits = [
+            (Interpolation::Linear, 0.95, 0.01),
+            (Interpolation::CubicHermite, 0.995, 0.01),
+            (Interpolation::Lagrange, 0.995, 0.001),
+            (Interpolation::AllPass, 1.0 - precision, 0.025),
+        ];
+        for (interpolation, min_gain, max_delay_error) in limits {
+            let (gain, delay_error) = measure::<T>(interpolation, 10.0, 100);
+            assert!((gain - 1.0).abs() < precision && delay_error.abs() < precision, "{:?}", interpolation);
+
+            for delay_samples in [10.25, 10.5, 10.75] {
+                let (gain, delay_error) = measure::<T>(interpolation, delay_samples, 10);
+                assert!((gain - 1.0).abs() < 1e-3 && delay_error.abs() < 1e-3, "{:?} {}", interpolation, delay_samples);
+
+                let (gain, delay_error) = measure::<T>(interpolation, delay_samples, 100);
+                assert!(
+                    gain >= min_gain && gain < 1.0 + precision && delay_error.abs() < max_delay_error,
+                    "{:?} {}: {} {}",
+                    interpolation,
+                    delay_samples,
+                    gain,
+                    delay_error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpolation_frequency_response() {
+        interpolation_frequency_response::<f32>(1e-5);
+        interpolation_frequency_response::<f64>(1e-9);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::float::Float;
+
+const DEFAULT_ATTACK_MS: f64 = 10.0;
+const DEFAULT_RELEASE_MS: f64 = 250.0;
+
+/// Envelope follower that turns a key signal into a gain for the wet signal,
+/// so the tail backs off while the key is loud and swells back in the gaps.
+pub struct Ducker<T: Float = f64> {
+    envelope: T,
+    attack_ms: f64,
+    release_ms: f64,
+    attack_coeff: T,
+    release_coeff: T,
+    amount: T,
+    sample_rate: f64,
+}
+
+impl<T: Float> Ducker<T> {
+    pub fn new(sample_rate: f64) -> Self {
+        Ducker {
+            envelope: T::ZERO,
+            attack_ms: DEFAULT_ATTACK_MS,
+            release_ms: DEFAULT_RELEASE_MS,
+            attack_coeff: Self::coefficient(DEFAULT_ATTACK_MS, sample_rate),
+            release_coeff: Self::coefficient(DEFAULT_RELEASE_MS, sample_rate),
+            amount: T::ZERO,
+            sample_rate,
+        }
+    }
+
+    /// Time the envelope takes to rise by 63% of a step in the key.
+    pub fn set_attack(&mut self, attack_ms: f64) {
+        if attack_ms == self.attack_ms {
+            return;
+        }
+        self.attack_ms = attack_ms;
+        self.attack_coeff = Self::coefficient(attack_ms, self.sample_rate);
+    }
+
+    /// Time the envelope takes to fall by 63% once the key stops.
+    pub fn set_release(&mut self, release_ms: f64) {
+        if release_ms == self.release_ms {
+            return;
+        }
+        self.release_ms = release_ms;
+        self.release_coeff = Self::coefficient(release_ms, self.sample_rate);
+    }
+
+    /// Gain reduction at a full-scale key: 0.0 never ducks, 1.0 mutes the wet
+    /// signal.
+    pub fn set_amount(&mut self, amount: f64) {
+        self.amount = T::from_f64(amount.clamp(0.0, 1.0));
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = T::ZERO;
+    }
+
+    /// One-pole coefficient for `time_ms`. A time of zero or less follows
+    /// the key instantly.
+    fn coefficient(time_ms: f64, sample_rate: f64) -> T {
+        if time_ms <= 0.0 {
+            return T::ZERO;
+        }
+        T::from_f64((-1000.0 / (time_ms * sample_rate)).exp())
+    }
+
+    /// Follows one sample of the key and returns the gain for the wet signal.
+    pub fn process(&mut self, key: T) -> T {
+        let key = key.abs();
+        let coeff = if key > self.envelope { self.attack_coeff } else { self.release_coeff };
+        self.envelope = coeff * self.envelope + (T::ONE - coeff) * key;
+        T::ONE - self.amount * self.envelope.min(T::ONE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attack_and_release_times() {
+        let sample_rate = 44100.0;
+        let mut uut = Ducker::new(sample_rate);
+        uut.set_attack(10.0);
+        uut.set_release(100.0);
+        uut.set_amount(0.5);
+
+        let attack_samples = (sample_rate * 0.01) as usize;
+        let gains: Vec<f64> = (0..attack_samples).map(|_| uut.process(1.0)).collect();
+        let attacked = 1.0 - (-1.0f64).exp();
+        assert!((gains[attack_samples - 1] - (1.0 - 0.5 * attacked)).abs() < 1e-3);
+
+        for _ in 0..attack_samples * 20 {
+            uut.process(-1.0);
+        }
+        assert!((uut.process(1.0) - 0.5).abs() < 1e-6);
+
+        let release_samples = (sample_rate * 0.1) as usize;
+        let gains: Vec<f64> = (0..release_samples).map(|_| uut.process(0.0)).collect();
+        assert!((gains[release_samples - 1] - (1.0 - 0.5 * (-1.0f64).exp())).abs() < 1e-3);
+        assert!(gains.windows(2).all(|pair| pair[1] > pair[0]));
+    }
+
+    #[test]
+    fn test_zero_times_follow_the_key() {
+        let mut uut = Ducker::new(44100.0);
+        uut.set_amount(1.0);
+        uut.set_attack(0.0);
+        uut.set_release(0.0);
+        assert_eq!(uut.process(0.5), 0.5);
+        assert_eq!(uut.process(0.0), 1.0);
+    }
+}
@@ -0,0 +1,276 @@
+use std::f64::consts::FRAC_PI_4;
+use nih_plug::prelude::Enum;
+use crate::delay_line::DelayLine;
+use crate::float::Float;
+use crate::reverb::get_length_in_samples;
+
+/// First- and second-order image sources of a shoebox room.
+const MAX_TAPS: usize = 24;
+const MAX_ORDER: i32 = 2;
+const SPEED_OF_SOUND_M_S: f64 = 343.0;
+/// Longer than the last second-order reflection of the church.
+pub(crate) const ER_MAX_DELAY_MS: f64 = 400.0;
+/// Length of the crossfade from the old room's taps to the new room's.
+const ROOM_CROSSFADE_MS: f64 = 50.0;
+/// Source and listener positions as fractions of the room's width, depth
+/// and height. Off-centre, so mirrored images do not land on the same tap.
+const SOURCE_POSITION: [f64; 3] = [0.4, 0.25, 0.4];
+const LISTENER_POSITION: [f64; 3] = [0.55, 0.7, 0.35];
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Room {
+    #[name = "Small Room"]
+    Small,
+    Hall,
+    Plate,
+    Church,
+}
+
+impl Room {
+    /// Width, depth and height in metres, and the wall reflection coefficient.
+    fn geometry(self) -> ([f64; 3], f64) {
+        match self {
+            Room::Small => ([5.0, 4.0, 3.0], 0.8),
+            Room::Hall => ([30.0, 20.0, 12.0], 0.85),
+            // A plate has no real room; a small, very reflective box gives the
+            // dense, bright cluster of taps a plate starts with.
+            Room::Plate => ([2.0, 1.5, 0.5], 0.95),
+            Room::Church => ([60.0, 25.0, 25.0], 0.9),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Tap<T: Float> {
+    delay_samples: f64,
+    left_gain: T,
+    right_gain: T,
+}
+
+/// Multi-tap delay producing a stereo early-reflection pattern. The taps are
+/// the image sources of a shoebox room: each one is delayed by its extra path
+/// length over the direct sound, attenuated by distance and wall absorption,
+/// and panned by the side it arrives from. A room change crossfades from the
+/// old taps to the new ones.
+pub struct EarlyReflections<T: Float = f64> {
+    delay_line: DelayLine<T>,
+    /// Taps of the room in use and of the room being faded to.
+    taps: [[Tap<T>; MAX_TAPS]; 2],
+    num_taps: [usize; 2],
+    /// Index into `taps` of the room in use.
+    current: usize,
+    room: Room,
+    next_room: Room,
+    target_room: Room,
+    fade_position: f64,
+    fade_step: f64,
+    sample_rate: f64,
+}
+
+impl<T: Float> EarlyReflections<T> {
+    pub fn new(sample_rate: f64) -> Self {
+        let max_delay_length = get_length_in_samples(ER_MAX_DELAY_MS, sample_rate).ceil() as usize;
+        let mut early_reflections = EarlyReflections {
+            delay_line: DelayLine::new(max_delay_length),
+            taps: [[Tap { delay_samples: 0.0, left_gain: T::ZERO, right_gain: T::ZERO }; MAX_TAPS]; 2],
+            num_taps: [0; 2],
+            current: 0,
+            room: Room::Small,
+            next_room: Room::Small,
+            target_room: Room::Small,
+            fade_position: 0.0,
+            fade_step: 1.0 / get_length_in_samples(ROOM_CROSSFADE_MS, sample_rate),
+            sample_rate,
+        };
+        early_reflections.build_taps(Room::Small, 0);
+        early_reflections
+    }
+
+    /// Crossfades to the taps of `room`. A change requested mid-fade starts
+    /// once it is done.
+    pub fn set_room(&mut self, room: Room) {
+        self.target_room = room;
+    }
+
+    /// Silences the reflections and moves to the requested room at once.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.fade_position = 0.0;
+        if self.target_room != self.room {
+            self.room = self.target_room;
+            self.build_taps(self.room, self.current);
+        }
+    }
+
+    /// Lays out the taps of `room` in `taps[set]`.
+    fn build_taps(&mut self, room: Room, set: usize) {
+        let (dimensions, reflection) = room.geometry();
+        let source: [f64; 3] = std::array::from_fn(|axis| SOURCE_POSITION[axis] * dimensions[axis]);
+        let listener: [f64; 3] = std::array::from_fn(|axis| LISTENER_POSITION[axis] * dimensions[axis]);
+        let direct_m = distance(source, listener);
+
+        let mut gains = [(0.0, 0.0); MAX_TAPS];
+        let (taps, num_taps) = (&mut self.taps[set], &mut self.num_taps[set]);
+        *num_taps = 0;
+        for x in -MAX_ORDER..=MAX_ORDER {
+            for y in -MAX_ORDER..=MAX_ORDER {
+                for z in -MAX_ORDER..=MAX_ORDER {
+                    let order = x.abs() + y.abs() + z.abs();
+                    if order == 0 || order > MAX_ORDER {
+                        continue;
+                    }
+                    // Mirroring the source `n` times along an axis puts it at
+                    // n * L + s for even `n` and (n + 1) * L - s for odd `n`.
+                    let image: [f64; 3] = std::array::from_fn(|axis| {
+                        let n = [x, y, z][axis];
+                        if n % 2 == 0 {
+                            n as f64 * dimensions[axis] + source[axis]
+                        } else {
+                            (n + 1) as f64 * dimensions[axis] - source[axis]
+                        }
+                    });
+                    let path_m = distance(image, listener);
+                    let gain = direct_m / path_m * reflection.powi(order);
+                    // -1.0 is hard left, 1.0 hard right.
+                    let pan = (image[0] - listener[0]) / path_m;
+                    let angle = (pan + 1.0) * FRAC_PI_4;
+
+                    taps[*num_taps].delay_samples = get_length_in_samples(
+                        (path_m - direct_m) / SPEED_OF_SOUND_M_S * 1000.0,
+                        self.sample_rate,
+                    );
+                    gains[*num_taps] = (gain * angle.cos(), gain * angle.sin());
+                    *num_taps += 1;
+                }
+            }
+        }
+
+        // Same normalisation as Moorer's tap line, per channel.
+        let gains = &gains[..*num_taps];
+        let left_sum: f64 = gains.iter().map(|(left, _)| left).sum();
+        let right_sum: f64 = gains.iter().map(|(_, right)| right).sum();
+        let norm = 1.0 / left_sum.max(right_sum);
+        for (tap, (left, right)) in taps.iter_mut().zip(gains.iter()) {
+            tap.left_gain = T::from_f64(left * norm);
+            tap.right_gain = T::from_f64(right * norm);
+        }
+    }
+
+    /// Feeds one mono sample and returns the (left, right) reflections.
+    pub fn process(&mut self, input: T) -> (T, T) {
+        self.delay_line.write(input);
+        if self.fade_position == 0.0 {
+            if self.target_room == self.room {
+                return self.read_taps(self.current);
+            }
+            self.next_room = self.target_room;
+            self.build_taps(self.next_room, 1 - self.current);
+        }
+
+        self.fade_position = (self.fade_position + self.fade_step).min(1.0);
+        let (old_left, old_right) = self.read_taps(self.current);
+        let (new_left, new_right) = self.read_taps(1 - self.current);
+        let (fade_in, fade_out) = (T::from_f64(self.fade_position), T::from_f64(1.0 - self.fade_position));
+        if self.fade_position >= 1.0 {
+            self.room = self.next_room;
+            self.current = 1 - self.current;
+            self.fade_position = 0.0;
+        }
+        (old_left * fade_out + new_left * fade_in, old_right * fade_out + new_right * fade_in)
+    }
+
+    /// Sum of the reflections through `taps[set]`.
+    fn read_taps(&mut self, set: usize) -> (T, T) {
+        self.taps[set][..self.num_taps[set]].iter().fold((T::ZERO, T::ZERO), |(left, right), tap| {
+            let reflection = self.delay_line.read(tap.delay_samples);
+            (left + reflection * tap.left_gain, right + reflection * tap.right_gain)
+        })
+    }
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::max_step;
+
+    fn first_and_last_tap_ms(room: Room) -> (f64, f64) {
+        let sample_rate = 44100.0;
+        let mut uut = EarlyReflections::<f64>::new(sample_rate);
+        uut.set_room(room);
+        uut.reset();
+        let delays = uut.taps[uut.current][..uut.num_taps[uut.current]].iter().map(|tap| tap.delay_samples * 1000.0 / sample_rate);
+        (delays.clone().fold(f64::MAX, f64::min), delays.fold(0.0, f64::max))
+    }
+
+    #[test]
+    fn test_tap_times_scale_with_room_size() {
+        let small = first_and_last_tap_ms(Room::Small);
+        let hall = first_and_last_tap_ms(Room::Hall);
+        let church = first_and_last_tap_ms(Room::Church);
+
+        assert!(first_and_last_tap_ms(Room::Plate).1 < small.1);
+        assert!(small.1 < hall.1 && hall.1 < church.1);
+        assert!(small.0 < hall.0 && hall.0 < church.0);
+        assert!(church.1 < ER_MAX_DELAY_MS);
+    }
+
+    #[test]
+    fn test_impulse_response_is_panned_and_finite() {
+        let sample_rate = 44100.0;
+        for room in [Room::Small, Room::Hall, Room::Plate, Room::Church] {
+            let mut uut = EarlyReflections::new(sample_rate);
+            uut.set_room(room);
+            uut.reset();
+            let out: Vec<(f64, f64)> = (0..get_length_in_samples(ER_MAX_DELAY_MS + 10.0, sample_rate) as usize)
+                .map(|n| uut.process(if n == 0 { 1.0 } else { 0.0 }))
+                .collect();
+
+            let left: f64 = out.iter().map(|(left, _)| left).sum();
+            let right: f64 = out.iter().map(|(_, right)| right).sum();
+            assert!(left.max(right) > 0.99 && left.max(right) < 1.0 + 1e-9, "{:?}", room);
+            assert!(out.iter().any(|(left, right)| left != right), "{:?}", room);
+            assert_eq!(*out.last().unwrap(), (0.0, 0.0));
+        }
+    }
+
+    /// Largest sample-to-sample step of either output over `num_samples` of a
+    /// steady sine.
+    fn early_max_step(uut: &mut EarlyReflections, start: usize, num_samples: usize) -> f64 {
+        max_step(start, num_samples, |input| {
+            let (left, right) = uut.process(input);
+            [left, right]
+        })
+    }
+
+    #[test]
+    fn test_room_change_is_click_free() {
+        let sample_rate = 44100.0;
+        let mut uut = EarlyReflections::new(sample_rate);
+        // Every tap steps in as the sine first reaches it.
+        early_max_step(&mut uut, 0, 22050);
+
+        let mut before = early_max_step(&mut uut, 22050, 22050);
+        let mut start = 44100;
+        for room in [Room::Church, Room::Plate, Room::Hall, Room::Small] {
+            // The change lands inside the window, so the step into the first
+            // sample after it is measured too.
+            let mut n = 0;
+            let during = max_step(start, 4410, |input| {
+                if n == 1 {
+                    uut.set_room(room);
+                }
+                n += 1;
+                let (left, right) = uut.process(input);
+                [left, right]
+            });
+            let after = early_max_step(&mut uut, start + 4410, 22050);
+            assert!(during <= 1.1 * before.max(after), "{:?}: {} vs {} / {}", room, during, before, after);
+            before = after;
+            start += 4410 + 22050;
+        }
+    }
+}
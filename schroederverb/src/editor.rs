@@ -9,6 +9,7 @@ use nih_plug_vizia::{assets, create_vizia_editor, ViziaState, ViziaTheming};
 
 use crate::audio_knob::KnobWidget;
 use crate::plugin::SchroederParams;
+use crate::reverb::Algorithm;
 use crate::colors::*;
 
 
@@ -20,7 +21,7 @@ struct Data {
 impl Model for Data{}
 
 pub(crate) fn default_state() -> Arc<ViziaState>{
-    ViziaState::new(||(600, 300))
+    ViziaState::new(||(600, 750))
 }
 
 pub(crate) fn create(
@@ -56,23 +57,112 @@ pub(crate) fn create(
             HStack::new(cx, |cx|{                            
                 HStack::new(cx, |cx|{                            
                     KnobWidget::new(cx, Data::params, |params| &params.rt60, false);
+                    Binding::new(cx, Data::params.map(|val| val.pre_delay_sync.value()), |cx, lens| {
+                        if lens.get(cx) {
+                            KnobWidget::new(cx, Data::params, |params| &params.pre_delay_division, false);
+                        } else {
+                            KnobWidget::new(cx, Data::params, |params| &params.pre_delay, false);
+                        };
+                    });
                     KnobWidget::new(cx, Data::params, |params| &params.dampening, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.er_balance, false);
                     KnobWidget::new(cx, Data::params, |params| &params.dry_wet_mix, false);
-                    Binding::new(cx, Data::params.map(|val| val.mod_enabled.value()), |cx, lens| {
-                        let value = lens.get(cx);
-                        if value {
-                            KnobWidget::new(cx, Data::params, |params| &params.mod_freq, false);
+                }).width(Stretch(0.75))        
+                .child_top(Pixels(30.0))
+                .height(Pixels(150.0));
+            });
+
+            HStack::new(cx, |cx|{                            
+                HStack::new(cx, |cx|{                            
+                    KnobWidget::new(cx, Data::params, |params| &params.diffusion, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.stereo_spread, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.width, false);
+                    Binding::new(cx, Data::params.map(|val| (val.mod_enabled.value(), val.mod_sync.value())), |cx, lens| {
+                        match lens.get(cx) {
+                            (true, true) => {
+                                KnobWidget::new(cx, Data::params, |params| &params.mod_division, false);
+                                KnobWidget::new(cx, Data::params, |params| &params.mod_depth, false);
+                                KnobWidget::new(cx, Data::params, |params| &params.mod_waveform, false);
+                            }
+                            (true, false) => {
+                                KnobWidget::new(cx, Data::params, |params| &params.mod_freq, false);
+                                KnobWidget::new(cx, Data::params, |params| &params.mod_depth, false);
+                                KnobWidget::new(cx, Data::params, |params| &params.mod_waveform, false);
+                            }
+                            _ => (),
                         };
                     });  
+                    // Only the Schroeder combs can be modulated or resized.
+                    Binding::new(cx, Data::params.map(|val| val.algorithm.value() == Algorithm::Schroeder), |cx, lens| {
+                        if lens.get(cx) {
+                            KnobWidget::new(cx, Data::params, |params| &params.size, false);
+                            KnobWidget::new(cx, Data::params, |params| &params.comb_mod_depth, false);
+                            KnobWidget::new(cx, Data::params, |params| &params.comb_mod_rate, false);
+                        };
+                    });
             
             
                 }).width(Stretch(0.75))        
                 .child_top(Pixels(30.0))
-                .height(Pixels(100.0));
+                .height(Pixels(150.0));
             });
 
-            ParamButton::new(cx, Data::params,  |params| &params.mod_enabled)
-            .space(Stretch(0.1));
+            HStack::new(cx, |cx|{
+                HStack::new(cx, |cx|{
+                    KnobWidget::new(cx, Data::params, |params| &params.duck_amount, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.duck_attack, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.duck_release, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.low_cut, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.high_cut, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.tilt, true);
+                }).width(Stretch(0.75))
+                .child_top(Pixels(30.0))
+                .height(Pixels(150.0));
+            });
+
+            HStack::new(cx, |cx|{
+                HStack::new(cx, |cx|{
+                    KnobWidget::new(cx, Data::params, |params| &params.low_decay, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.high_decay, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.low_crossover, false);
+                    KnobWidget::new(cx, Data::params, |params| &params.high_crossover, false);
+                }).width(Stretch(0.75))
+                .child_top(Pixels(30.0))
+                .height(Pixels(150.0));
+            });
+
+            HStack::new(cx, |cx|{
+                ParamSlider::new(cx, Data::params, |params| &params.algorithm)
+                .space(Stretch(0.1));
+                ParamSlider::new(cx, Data::params, |params| &params.room)
+                .space(Stretch(0.1));
+                ParamButton::new(cx, Data::params,  |params| &params.mod_enabled)
+                .space(Stretch(0.1));
+                ParamButton::new(cx, Data::params,  |params| &params.mod_sync)
+                .space(Stretch(0.1));
+                ParamButton::new(cx, Data::params,  |params| &params.pre_delay_sync)
+                .space(Stretch(0.1));
+                ParamButton::new(cx, Data::params,  |params| &params.duck_sidechain)
+                .space(Stretch(0.1));
+                // Only the Schroeder network can freeze its tail.
+                Binding::new(cx, Data::params.map(|val| val.algorithm.value() == Algorithm::Schroeder), |cx, lens| {
+                    if lens.get(cx) {
+                        ParamButton::new(cx, Data::params,  |params| &params.freeze)
+                        .space(Stretch(0.1));
+                    };
+                });
+            });
+
+            Binding::new(cx, Data::params.map(|val| val.duck_sidechain.value()), |cx, lens| {
+                if lens.get(cx) {
+                    Label::new(cx, "The input keys the ducker until the sidechain carries signal")
+                    .font_size(12.0)
+                    .color(YELLOW_MUSTARD)
+                    .width(Stretch(1.0))
+                    .child_left(Stretch(1.0))
+                    .child_right(Stretch(1.0));
+                };
+            });
            
        }).row_between(Pixels(0.0))
        .background_color(DARK_GREY_UI_COLOR);
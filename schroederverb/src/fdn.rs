@@ -0,0 +1,330 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::decay_filter::BandDecayFilter;
+use crate::delay_line::{DelayCrossfade, DelayLine};
+use crate::float::Float;
+use crate::reverb::{
+    get_band_gains_from_rt60, get_length_in_samples, Reverb, DEFAULT_HIGH_CROSSOVER_HZ, DEFAULT_LOW_CROSSOVER_HZ,
+};
+
+const LINE_MIN_DELAY_MS: f64 = 23.0;
+const LINE_MAX_DELAY_MS: f64 = 71.0;
+/// Room for the stereo offset and for rounding lengths up to a prime.
+const LINE_BUFFER_MS: f64 = 80.0;
+/// Ratio of the high-frequency to the low-frequency decay time at full
+/// dampening.
+const HIGH_DECAY_MIN_RATIO: f64 = 0.1;
+const RANDOM_MATRIX_SEED: u32 = 0x5eed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixingMatrix {
+    /// Normalised Sylvester Hadamard matrix. Needs a power-of-two size.
+    Hadamard,
+    /// Reflection `I - 2/N * 1 1^T`.
+    Householder,
+    /// Orthonormalised pseudo-random matrix, always built from the same seed.
+    RandomOrthogonal,
+}
+
+impl MixingMatrix {
+    fn build<const N: usize>(self) -> [[f64; N]; N] {
+        match self {
+            MixingMatrix::Hadamard => {
+                assert!(N.is_power_of_two(), "Hadamard mixing needs a power-of-two size");
+                let norm = 1.0 / (N as f64).sqrt();
+                std::array::from_fn(|row| {
+                    std::array::from_fn(|col| if (row & col).count_ones() % 2 == 0 { norm } else { -norm })
+                })
+            }
+            MixingMatrix::Householder => std::array::from_fn(|row| {
+                std::array::from_fn(|col| if row == col { 1.0 } else { 0.0 } - 2.0 / N as f64)
+            }),
+            MixingMatrix::RandomOrthogonal => {
+                let mut seed = RANDOM_MATRIX_SEED;
+                let mut matrix: [[f64; N]; N] = std::array::from_fn(|_| {
+                    std::array::from_fn(|_| {
+                        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                        (seed as f64 / u32::MAX as f64) * 2.0 - 1.0
+                    })
+                });
+                // Gram-Schmidt on the rows.
+                for row in 0..N {
+                    for previous in 0..row {
+                        let projection: f64 = (0..N).map(|col| matrix[row][col] * matrix[previous][col]).sum();
+                        let previous_row = matrix[previous];
+                        for (x, p) in matrix[row].iter_mut().zip(previous_row.iter()) {
+                            *x -= projection * p;
+                        }
+                    }
+                    let norm = matrix[row].iter().map(|x| x * x).sum::<f64>().sqrt();
+                    matrix[row].iter_mut().for_each(|x| *x /= norm);
+                }
+                matrix
+            }
+        }
+    }
+}
+
+fn next_prime(n: usize) -> usize {
+    let is_prime = |n: usize| n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d));
+    (n..).find(|n| is_prime(*n)).unwrap()
+}
+
+/// Feedback delay network of `N` lines. The line outputs go through a
+/// per-line absorption filter and a lossless mixing matrix before being fed
+/// back, so the decay is set by the filters alone. Following Jot, each filter
+/// is designed from its line's length so the whole network reaches the RT60 in
+/// the mid band, and the RT60 scaled by the low and high multipliers below and
+/// above the crossovers. Dampening shortens the high band further.
+pub struct Fdn<T: Float, const N: usize> {
+    lines: [DelayLine<T>; N],
+    /// Read positions of the lines, which crossfade when the offset moves.
+    delays: [DelayCrossfade; N],
+    delays_ms: [f64; N],
+    /// Each loop's length before it is rounded up to a prime.
+    rounded_lengths: [usize; N],
+    loop_lengths: [f64; N],
+    matrix: [[T; N]; N],
+    filters: [BandDecayFilter<T>; N],
+    filter_outputs: [T; N],
+    sample_rate: f64,
+    rt60_ms: f64,
+    delay_offset_ms: f64,
+    dampening: f64,
+    low_multiplier: f64,
+    high_multiplier: f64,
+    crossovers_hz: (f64, f64),
+}
+
+impl<T: Float, const N: usize> Fdn<T, N> {
+    pub fn new(sample_rate: f64, mixing: MixingMatrix) -> Self {
+        let line_length = get_length_in_samples(LINE_BUFFER_MS, sample_rate).ceil() as usize;
+        let spread = LINE_MAX_DELAY_MS / LINE_MIN_DELAY_MS;
+
+        Fdn {
+            lines: std::array::from_fn(|_| DelayLine::new(line_length)),
+            delays: std::array::from_fn(|_| DelayCrossfade::new(1.0)),
+            delays_ms: std::array::from_fn(|index| {
+                LINE_MIN_DELAY_MS * spread.powf(index as f64 / (N - 1).max(1) as f64)
+            }),
+            rounded_lengths: [0; N],
+            loop_lengths: [0.0; N],
+            matrix: mixing.build().map(|row| row.map(T::from_f64)),
+            filters: std::array::from_fn(|_| BandDecayFilter::new()),
+            filter_outputs: [T::ZERO; N],
+            sample_rate,
+            rt60_ms: 0.0,
+            delay_offset_ms: 0.0,
+            dampening: 0.0,
+            low_multiplier: 1.0,
+            high_multiplier: 1.0,
+            crossovers_hz: (DEFAULT_LOW_CROSSOVER_HZ, DEFAULT_HIGH_CROSSOVER_HZ),
+        }
+    }
+
+    /// Works out each loop's length at the current offset. Finding the prime
+    /// is skipped for the loops whose rounded length has not moved, which is
+    /// most of them while the offset glides.
+    fn update_loop_lengths(&mut self) {
+        for index in 0..N {
+            let length = get_length_in_samples(self.delays_ms[index] + self.delay_offset_ms, self.sample_rate);
+            let rounded_length = length.round() as usize;
+            if rounded_length != self.rounded_lengths[index] {
+                self.rounded_lengths[index] = rounded_length;
+                // Mutually prime loop lengths keep the echoes from piling up.
+                self.loop_lengths[index] = next_prime(rounded_length) as f64;
+            }
+        }
+    }
+
+    fn update_absorption(&mut self) {
+        let high_multiplier = self.high_multiplier * (1.0 - self.dampening * (1.0 - HIGH_DECAY_MIN_RATIO));
+        for index in 0..N {
+            let loop_ms = self.loop_lengths[index] * 1000.0 / self.sample_rate;
+            let gains = get_band_gains_from_rt60(loop_ms, self.rt60_ms, (self.low_multiplier, high_multiplier));
+            self.filters[index].design(gains, self.crossovers_hz, self.sample_rate);
+        }
+    }
+}
+
+impl<T: Float, const N: usize> Reverb<T> for Fdn<T, N> {
+    fn prepare(&mut self, sample_rate: f64, rt60_ms: f64) {
+        self.sample_rate = sample_rate;
+        self.rt60_ms = rt60_ms;
+
+        self.update_loop_lengths();
+        for (delay, loop_length) in self.delays.iter_mut().zip(self.loop_lengths.iter()) {
+            delay.jump(*loop_length);
+        }
+        self.update_absorption();
+    }
+
+    fn update_reverb_time(&mut self, rt60_ms: f64) {
+        if rt60_ms == self.rt60_ms {
+            return;
+        }
+        self.rt60_ms = rt60_ms;
+        self.update_absorption();
+    }
+
+    /// 0.0 leaves the band decay times as set, 1.0 makes the highs die out ten
+    /// times faster on top of that.
+    fn set_dampening(&mut self, dampening: f64) {
+        let dampening = dampening.clamp(0.0, 1.0);
+        if dampening == self.dampening {
+            return;
+        }
+        self.dampening = dampening;
+        self.update_absorption();
+    }
+
+    fn set_band_decay(&mut self, low_multiplier: f64, high_multiplier: f64) {
+        let high_multiplier = high_multiplier.min(1.0);
+        if (low_multiplier, high_multiplier) == (self.low_multiplier, self.high_multiplier) {
+            return;
+        }
+        self.low_multiplier = low_multiplier;
+        self.high_multiplier = high_multiplier;
+        self.update_absorption();
+    }
+
+    fn set_crossovers(&mut self, low_hz: f64, high_hz: f64) {
+        if (low_hz, high_hz) == self.crossovers_hz {
+            return;
+        }
+        self.crossovers_hz = (low_hz, high_hz);
+        self.update_absorption();
+    }
+
+    fn set_delay_offset(&mut self, offset_ms: f64) {
+        if offset_ms == self.delay_offset_ms {
+            return;
+        }
+        self.delay_offset_ms = offset_ms;
+        self.update_loop_lengths();
+        for (delay, loop_length) in self.delays.iter_mut().zip(self.loop_lengths.iter()) {
+            delay.set_target(*loop_length);
+        }
+        self.update_absorption();
+    }
+
+    fn max_delay_ms(&self) -> f64 {
+        LINE_BUFFER_MS
+    }
+
+    fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.reset();
+        }
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+    }
+
+    fn process_wet(&mut self, input: T) -> T {
+        self.process(input)
+    }
+}
+
+impl<T: Float, const N: usize> AudioProcessor<T> for Fdn<T, N> {
+    fn process(&mut self, input: T) -> T {
+        // Lines are read before they are written, which adds one sample to
+        // every loop.
+        let outputs: [T; N] = std::array::from_fn(|index| {
+            let line = &mut self.lines[index];
+            self.delays[index].read(|delay, head| line.read_head(delay, head), -1.0)
+        });
+
+        let filtered = self.filter_outputs.iter_mut().zip(outputs.iter());
+        for (filter, (filtered, output)) in self.filters.iter_mut().zip(filtered) {
+            *filtered = filter.process(*output);
+        }
+
+        for (line, row) in self.lines.iter_mut().zip(self.matrix.iter()) {
+            let feedback = row.iter().zip(self.filter_outputs.iter()).fold(T::ZERO, |sum, (m, x)| sum + *m * *x);
+            line.write(input + feedback);
+        }
+
+        outputs.iter().fold(T::ZERO, |sum, output| sum + *output) / T::from_f64(N as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reverb::{measure_band_rt60_s, measure_rt60_s};
+    use crate::test_util::assert_impulse_response_decays;
+
+    #[test]
+    fn test_impulse_response_decays() {
+        let sample_rate = 44100.0;
+        let mut uut = Fdn::<f64, 8>::new(sample_rate, MixingMatrix::Householder);
+        uut.prepare(sample_rate, 1000.0);
+        uut.set_dampening(0.5);
+
+        assert_impulse_response_decays(&mut uut);
+    }
+
+    fn assert_orthogonal<const N: usize>(mixing: MixingMatrix) {
+        let matrix: [[f64; N]; N] = mixing.build();
+        for row in 0..N {
+            for other in 0..N {
+                let dot: f64 = (0..N).map(|col| matrix[row][col] * matrix[other][col]).sum();
+                let expected = if row == other { 1.0 } else { 0.0 };
+                assert!((dot - expected).abs() < 1e-12, "{:?} {}x{}", mixing, N, N);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mixing_matrices_are_lossless() {
+        for mixing in [MixingMatrix::Hadamard, MixingMatrix::Householder, MixingMatrix::RandomOrthogonal] {
+            assert_orthogonal::<4>(mixing);
+            assert_orthogonal::<8>(mixing);
+            assert_orthogonal::<16>(mixing);
+        }
+    }
+
+    fn render_rt60_s<const N: usize>(mixing: MixingMatrix, rt60_ms: f64) -> f64 {
+        let sample_rate = 44100.0;
+        let mut uut = Fdn::<f64, N>::new(sample_rate, mixing);
+        uut.prepare(sample_rate, rt60_ms);
+
+        let num_samples = (sample_rate * rt60_ms / 1000.0) as usize;
+        let impulse_response: Vec<f64> = (0..num_samples)
+            .map(|n| uut.process(if n == 0 { 1.0 } else { 0.0 }))
+            .collect();
+        measure_rt60_s(&impulse_response, sample_rate)
+    }
+
+    #[test]
+    fn test_measured_rt60_matches_target() {
+        for rt60_ms in [500.0, 2000.0] {
+            let target_s = rt60_ms / 1000.0;
+            for rt60_s in [
+                render_rt60_s::<4>(MixingMatrix::Householder, rt60_ms),
+                render_rt60_s::<8>(MixingMatrix::Hadamard, rt60_ms),
+                render_rt60_s::<16>(MixingMatrix::RandomOrthogonal, rt60_ms),
+            ] {
+                assert!((rt60_s - target_s).abs() / target_s < 0.1, "{} s vs {} s", rt60_s, target_s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_band_decay_times() {
+        let sample_rate = 44100.0;
+        let mut uut = Fdn::<f64, 8>::new(sample_rate, MixingMatrix::Hadamard);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_crossovers(200.0, 6000.0);
+        uut.set_band_decay(1.5, 0.5);
+
+        let impulse_response: Vec<f64> = (0..(sample_rate * 2.5) as usize)
+            .map(|n| uut.process(if n == 0 { 1.0 } else { 0.0 }))
+            .collect();
+
+        for (band_hz, target_s) in [((40.0, 80.0), 3.0), ((1000.0, 2000.0), 2.0), ((14000.0, 18000.0), 1.0)] {
+            let rt60_s = measure_band_rt60_s(&impulse_response, sample_rate, band_hz);
+            assert!((rt60_s - target_s).abs() / target_s < 0.15, "{:?}: {} s vs {} s", band_hz, rt60_s, target_s);
+        }
+    }
+}
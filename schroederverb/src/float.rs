@@ -0,0 +1,89 @@
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub};
+use wide::{f32x4, f64x4};
+
+/// Samples in a `Float::Vector`.
+pub const LANES: usize = 4;
+
+/// Sample type the reverb runs on, so the same code can process `f32`
+/// natively and `f64` where the extra precision is worth it. Settings
+/// and coefficients are still worked out in `f64` and converted with
+/// `from_f64`; only the signal path uses `Self`.
+pub trait Float:
+    Copy
+    + Default
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + MulAssign
+    + DivAssign
+{
+    /// `LANES` samples processed together, for filters that run in lock-step.
+    type Vector: Copy + Add<Output = Self::Vector> + Sub<Output = Self::Vector> + Mul<Output = Self::Vector>;
+
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn abs(self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn min(self, other: Self) -> Self;
+
+    fn splat(value: Self) -> Self::Vector;
+    /// Loads the first `LANES` samples of `lanes`.
+    fn load(lanes: &[Self]) -> Self::Vector;
+    /// Stores `vector` into the first `LANES` samples of `lanes`.
+    fn store(vector: Self::Vector, lanes: &mut [Self]);
+}
+
+macro_rules! impl_float {
+    ($type:ty, $vector:ident) => {
+        impl Float for $type {
+            type Vector = $vector;
+
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+
+            fn from_f64(value: f64) -> Self {
+                value as $type
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn abs(self) -> Self {
+                <$type>::abs(self)
+            }
+
+            fn max(self, other: Self) -> Self {
+                <$type>::max(self, other)
+            }
+
+            fn min(self, other: Self) -> Self {
+                <$type>::min(self, other)
+            }
+
+            fn splat(value: Self) -> $vector {
+                $vector::splat(value)
+            }
+
+            fn load(lanes: &[Self]) -> $vector {
+                $vector::new(lanes[..LANES].try_into().unwrap())
+            }
+
+            fn store(vector: $vector, lanes: &mut [Self]) {
+                lanes[..LANES].copy_from_slice(&vector.to_array());
+            }
+        }
+    };
+}
+
+impl_float!(f32, f32x4);
+impl_float!(f64, f64x4);
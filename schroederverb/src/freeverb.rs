@@ -0,0 +1,174 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::all_pass::AllPass;
+use crate::delay_line::Interpolation;
+use crate::float::Float;
+use crate::mod_comb::ModComb;
+use crate::reverb::{
+    get_band_gains_from_rt60, get_length_in_samples, Reverb, DEFAULT_HIGH_CROSSOVER_HZ, DEFAULT_LOW_CROSSOVER_HZ,
+};
+
+const NUM_COMBS: usize = 8;
+const NUM_APF: usize = 4;
+/// Jezar's tunings, converted from samples at 44.1 kHz.
+const COMB_DELAYS_MS: [f64; NUM_COMBS] = [25.31, 26.94, 28.96, 30.75, 32.24, 33.81, 35.31, 36.67];
+const APF_DELAYS_MS: [f64; NUM_APF] = [12.61, 10.0, 7.73, 5.10];
+const APF_GAIN: f64 = 0.5;
+const COMB_MAX_DELAY_MS: f64 = 40.0;
+const APF_MAX_DELAY_MS: f64 = 15.0;
+
+/// Freeverb-style network: eight low-pass combs in parallel followed by four
+/// all-passes in series.
+pub struct Freeverb<T: Float = f64> {
+    combs: [(ModComb<T>, f64); NUM_COMBS],
+    all_passes: [(AllPass<T>, f64); NUM_APF],
+    sample_rate: f64,
+    rt60_ms: f64,
+    delay_offset_ms: f64,
+    dampening: f64,
+    low_multiplier: f64,
+    high_multiplier: f64,
+    crossovers_hz: (f64, f64),
+}
+
+impl<T: Float> Freeverb<T> {
+    pub fn new(sample_rate: f64) -> Self {
+        let comb_delay_length = get_length_in_samples(COMB_MAX_DELAY_MS, sample_rate) as usize;
+        let apf_delay_length = get_length_in_samples(APF_MAX_DELAY_MS, sample_rate) as usize;
+
+        Freeverb {
+            combs: COMB_DELAYS_MS.map(|delay_ms| (ModComb::new(comb_delay_length, Interpolation::Linear, sample_rate), delay_ms)),
+            all_passes: APF_DELAYS_MS.map(|delay_ms| (AllPass::new(apf_delay_length), delay_ms)),
+            sample_rate,
+            rt60_ms: 0.0,
+            delay_offset_ms: 0.0,
+            dampening: 0.0,
+            low_multiplier: 1.0,
+            high_multiplier: 1.0,
+            crossovers_hz: (DEFAULT_LOW_CROSSOVER_HZ, DEFAULT_HIGH_CROSSOVER_HZ),
+        }
+    }
+
+    /// Designs each comb's loop filter so its bands decay at their RT60s.
+    fn update_feedback(&mut self) {
+        let multipliers = (self.low_multiplier, self.high_multiplier);
+        for (comb, delay_ms) in self.combs.iter_mut() {
+            let gains = get_band_gains_from_rt60(*delay_ms + self.delay_offset_ms, self.rt60_ms, multipliers);
+            comb.set_band_gains(gains, self.crossovers_hz);
+        }
+    }
+}
+
+impl<T: Float> Reverb<T> for Freeverb<T> {
+    fn prepare(&mut self, sample_rate: f64, rt60_ms: f64) {
+        self.sample_rate = sample_rate;
+        self.rt60_ms = rt60_ms;
+
+        for (comb, delay_ms) in self.combs.iter_mut() {
+            comb.prepare(get_length_in_samples(*delay_ms + self.delay_offset_ms, sample_rate));
+        }
+        self.update_feedback();
+
+        for (all_pass, delay_ms) in self.all_passes.iter_mut() {
+            let delay_samples = get_length_in_samples(*delay_ms + self.delay_offset_ms, sample_rate);
+            all_pass.prepare(delay_samples, APF_GAIN);
+        }
+    }
+
+    fn update_reverb_time(&mut self, rt60_ms: f64) {
+        if rt60_ms == self.rt60_ms {
+            return;
+        }
+        self.rt60_ms = rt60_ms;
+        self.update_feedback();
+    }
+
+    fn set_dampening(&mut self, dampening: f64) {
+        if dampening == self.dampening {
+            return;
+        }
+        self.dampening = dampening;
+        for (comb, _) in self.combs.iter_mut() {
+            comb.set_dampening(dampening);
+        }
+    }
+
+    fn set_band_decay(&mut self, low_multiplier: f64, high_multiplier: f64) {
+        let high_multiplier = high_multiplier.min(1.0);
+        if (low_multiplier, high_multiplier) == (self.low_multiplier, self.high_multiplier) {
+            return;
+        }
+        self.low_multiplier = low_multiplier;
+        self.high_multiplier = high_multiplier;
+        self.update_feedback();
+    }
+
+    fn set_crossovers(&mut self, low_hz: f64, high_hz: f64) {
+        if (low_hz, high_hz) == self.crossovers_hz {
+            return;
+        }
+        self.crossovers_hz = (low_hz, high_hz);
+        self.update_feedback();
+    }
+
+    fn set_delay_offset(&mut self, offset_ms: f64) {
+        if offset_ms == self.delay_offset_ms {
+            return;
+        }
+        self.delay_offset_ms = offset_ms;
+        for (comb, delay_ms) in self.combs.iter_mut() {
+            comb.set_delay(get_length_in_samples(*delay_ms + offset_ms, self.sample_rate));
+        }
+        self.update_feedback();
+        for (all_pass, delay_ms) in self.all_passes.iter_mut() {
+            all_pass.set_delay(get_length_in_samples(*delay_ms + offset_ms, self.sample_rate));
+        }
+    }
+
+    fn max_delay_ms(&self) -> f64 {
+        COMB_MAX_DELAY_MS
+    }
+
+    fn reset(&mut self) {
+        for (comb, _) in self.combs.iter_mut() {
+            comb.reset();
+        }
+        for (all_pass, _) in self.all_passes.iter_mut() {
+            all_pass.reset();
+        }
+    }
+
+    fn process_wet(&mut self, input: T) -> T {
+        self.process(input)
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for Freeverb<T> {
+    fn process(&mut self, input: T) -> T {
+        let mut out = T::ZERO;
+        for (comb, _) in self.combs.iter_mut() {
+            out += comb.process(input);
+        }
+        out /= T::from_f64(NUM_COMBS as f64);
+
+        for (all_pass, _) in self.all_passes.iter_mut() {
+            out = all_pass.process(out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_impulse_response_decays;
+
+    #[test]
+    fn test_impulse_response_decays() {
+        let sample_rate = 44100.0;
+        let mut uut = Freeverb::<f64>::new(sample_rate);
+        uut.prepare(sample_rate, 1000.0);
+        uut.set_dampening(0.5);
+
+        assert_impulse_response_decays(&mut uut);
+    }
+}
@@ -0,0 +1,145 @@
+use std::f64::consts::{PI, TAU};
+use nih_plug::prelude::Enum;
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    /// A new random value every cycle, held for the whole cycle.
+    #[name = "S&H"]
+    SampleAndHold,
+    /// A new random value every cycle, glided to over the whole cycle.
+    #[name = "Smooth Random"]
+    SmoothRandom,
+}
+
+/// LFO running between -1.0 and 1.0. Unlike the euterpe_rs oscillator its
+/// phase can be set, so it can follow the host's bar position.
+pub struct Lfo {
+    phase: f64,
+    phase_increment: f64,
+    sample_rate: f64,
+    waveform: Waveform,
+    /// Random values the random waveforms move from and to.
+    previous_random: f64,
+    random: f64,
+    seed: u32,
+}
+
+impl Lfo {
+    pub fn new(freq_hz: f64, sample_rate: f64) -> Self {
+        Lfo {
+            phase: 0.0,
+            phase_increment: freq_hz / sample_rate,
+            sample_rate,
+            waveform: Waveform::Triangle,
+            previous_random: 0.0,
+            random: 0.0,
+            seed: 1,
+        }
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    pub fn set_freq(&mut self, freq_hz: f64) {
+        self.phase_increment = freq_hz / self.sample_rate;
+    }
+
+    /// `phase` is the position in the cycle, from 0.0 to 1.0.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Returns the current value and advances by one sample. The periodic
+    /// waveforms start at 0.0 and rise, like a sine; the square starts high.
+    pub fn next(&mut self) -> f64 {
+        let phase = self.phase;
+        let value = match self.waveform {
+            Waveform::Sine => (TAU * phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (wrap(phase + 0.25) - 0.5).abs(),
+            Waveform::Saw => 2.0 * wrap(phase + 0.5) - 1.0,
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::SampleAndHold => self.random,
+            Waveform::SmoothRandom => {
+                let glide = 0.5 - 0.5 * (PI * phase).cos();
+                self.previous_random + (self.random - self.previous_random) * glide
+            }
+        };
+
+        self.phase += self.phase_increment;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.rem_euclid(1.0);
+            self.previous_random = self.random;
+            self.seed = self.seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            self.random = (self.seed as f64 / u32::MAX as f64) * 2.0 - 1.0;
+        }
+        value
+    }
+}
+
+/// `phase` from [0.0, 2.0) back into [0.0, 1.0). The same as `rem_euclid(1.0)`
+/// over that range, without the `fmod` call that made it the dearest part of a
+/// modulated comb.
+pub(crate) fn wrap(phase: f64) -> f64 {
+    if phase >= 1.0 { phase - 1.0 } else { phase }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triangle_follows_phase() {
+        let mut uut = Lfo::new(1.0, 8.0);
+        let cycle: Vec<f64> = (0..8).map(|_| uut.next()).collect();
+        assert_eq!(cycle, [0.0, 0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -0.5]);
+
+        uut.set_phase(1.75);
+        assert_eq!(uut.next(), -1.0);
+        uut.set_freq(2.0);
+        assert_eq!(uut.next(), -0.5);
+        assert_eq!(uut.next(), 0.5);
+    }
+
+    fn cycle(waveform: Waveform) -> Vec<f64> {
+        let mut uut = Lfo::new(1.0, 8.0);
+        uut.set_waveform(waveform);
+        (0..8).map(|_| uut.next()).collect()
+    }
+
+    #[test]
+    fn test_periodic_waveforms() {
+        let sine = cycle(Waveform::Sine);
+        assert!((sine[2] - 1.0).abs() < 1e-12 && (sine[6] + 1.0).abs() < 1e-12);
+        assert_eq!(cycle(Waveform::Saw), [0.0, 0.25, 0.5, 0.75, -1.0, -0.75, -0.5, -0.25]);
+        assert_eq!(cycle(Waveform::Square), [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_random_waveforms() {
+        let mut held = Lfo::new(1.0, 64.0);
+        held.set_waveform(Waveform::SampleAndHold);
+        let mut smooth = Lfo::new(1.0, 64.0);
+        smooth.set_waveform(Waveform::SmoothRandom);
+
+        let held: Vec<f64> = (0..64 * 8).map(|_| held.next()).collect();
+        let smooth: Vec<f64> = (0..64 * 8).map(|_| smooth.next()).collect();
+        for cycle in held.chunks(64) {
+            assert!(cycle.iter().all(|x| *x == cycle[0]));
+        }
+        assert!(held.chunks(64).skip(1).any(|cycle| cycle[0] != held[0]));
+        assert!(held.iter().chain(smooth.iter()).all(|x| (-1.0..=1.0).contains(x)));
+        // Glides, so no jumps at the cycle boundaries.
+        assert!(smooth.windows(2).all(|pair| (pair[1] - pair[0]).abs() < 0.1));
+        // Lands on the value S&H holds for the cycle before.
+        assert_eq!(smooth[128], held[64]);
+    }
+}
@@ -1,4 +1,31 @@
-mod schroeder;
+//! `all_pass`, `mod_all_pass`, `mod_comb`, `comb_bank`, `delay_line` and
+//! `lfo` are staged here until they can move into euterpe_rs. Its `Comb`
+//! and `AllPass` lack what the networks rely on: an in-place reset,
+//! crossfading `set_delay`, a generic sample type and band-decay feedback.
+//! Once euterpe_rs has those, the networks should build on its blocks and
+//! these modules should go.
+
+pub mod all_pass;
+mod biquad;
+pub mod block;
+pub mod comb_bank;
+mod decay_filter;
+pub mod delay_line;
+mod ducker;
+mod early_reflections;
+pub mod float;
+mod lfo;
+pub mod mod_all_pass;
+pub mod mod_comb;
+pub mod reverb;
+pub mod schroeder;
+mod moorer;
+mod freeverb;
+mod fdn;
+pub mod stereo;
+mod tempo;
+#[cfg(test)]
+mod test_util;
 mod audio_knob;
 mod colors;
 // mod param_knob;
@@ -0,0 +1,89 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::block::BlockProcessor;
+use crate::delay_line::{DelayLine, Interpolation};
+use crate::float::Float;
+use crate::lfo::{Lfo, Waveform};
+use crate::reverb::get_length_in_samples;
+
+/// Schroeder all-pass whose delay is swept by an `Lfo`. Stands in for the
+/// euterpe_rs `ModAllPass` so the LFO phase can be locked to the host.
+pub struct ModAllPass<T: Float = f64> {
+    delay_line: DelayLine<T>,
+    lfo: Lfo,
+    delay_samples: f64,
+    depth_samples: f64,
+    max_depth_samples: f64,
+    gain: T,
+    sample_rate: f64,
+}
+
+impl<T: Float> ModAllPass<T> {
+    /// The line is sized for sweeps up to `max_depth_ms` either way of
+    /// `delay_ms`.
+    pub fn new(
+        delay_ms: f64,
+        depth_ms: f64,
+        max_depth_ms: f64,
+        lfo_freq_hz: f64,
+        gain: f64,
+        interpolation: Interpolation,
+        sample_rate: f64,
+    ) -> Self {
+        let delay_samples = get_length_in_samples(delay_ms, sample_rate);
+        let max_depth_samples = get_length_in_samples(max_depth_ms, sample_rate);
+
+        ModAllPass {
+            delay_line: DelayLine::with_interpolation((delay_samples + max_depth_samples).ceil() as usize, interpolation),
+            lfo: Lfo::new(lfo_freq_hz, sample_rate),
+            delay_samples,
+            depth_samples: get_length_in_samples(depth_ms.min(max_depth_ms), sample_rate),
+            max_depth_samples,
+            gain: T::from_f64(gain),
+            sample_rate,
+        }
+    }
+
+    pub fn set_depth(&mut self, depth_ms: f64) {
+        self.depth_samples = get_length_in_samples(depth_ms, self.sample_rate).clamp(0.0, self.max_depth_samples);
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.lfo.set_waveform(waveform);
+    }
+
+    pub fn set_lfo_freq(&mut self, freq_hz: f64) {
+        self.lfo.set_freq(freq_hz);
+    }
+
+    pub fn set_lfo_phase(&mut self, phase: f64) {
+        self.lfo.set_phase(phase);
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.lfo.reset();
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for ModAllPass<T> {
+    fn process(&mut self, input: T) -> T {
+        // Read before writing, so a read of `d - 1` is `d` samples old.
+        let delay = self.delay_samples + self.depth_samples * self.lfo.next();
+        let delayed = self.delay_line.read(delay - 1.0);
+        let feedback = input + self.gain * delayed;
+        self.delay_line.write(feedback);
+        delayed - self.gain * feedback
+    }
+}
+
+impl<T: Float> BlockProcessor<T> for ModAllPass<T> {
+    fn process_block_in_place(&mut self, buffer: &mut [T]) {
+        let (gain, delay_samples, depth_samples) = (self.gain, self.delay_samples, self.depth_samples);
+        for sample in buffer.iter_mut() {
+            let delayed = self.delay_line.read(delay_samples + depth_samples * self.lfo.next() - 1.0);
+            let feedback = *sample + gain * delayed;
+            self.delay_line.write(feedback);
+            *sample = delayed - gain * feedback;
+        }
+    }
+}
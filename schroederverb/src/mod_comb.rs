@@ -0,0 +1,149 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::block::BlockProcessor;
+use crate::decay_filter::BandDecayFilter;
+use crate::delay_line::{DelayCrossfade, DelayLine, Interpolation};
+use crate::float::Float;
+use crate::lfo::Lfo;
+
+/// Low-pass feedback comb whose delay can be swept by its own `Lfo`. The
+/// delay is read with interpolation, so it can be fractional and change
+/// from sample to sample, unlike the euterpe_rs `Comb`. `set_delay`
+/// crossfades to a new length. The unmodulated length is rounded to whole
+/// samples, so the reads are exact and a frozen tail keeps its highs. The
+/// feedback goes through a `BandDecayFilter`, so the low, mid and high bands
+/// can each have their own loop gain.
+pub struct ModComb<T: Float = f64> {
+    delay_line: DelayLine<T>,
+    lfo: Lfo,
+    delay: DelayCrossfade,
+    depth_samples: f64,
+    sample_rate: f64,
+    filter: BandDecayFilter<T>,
+    dampening: T,
+    filter_store: T,
+}
+
+impl<T: Float> ModComb<T> {
+    pub fn new(max_delay_samples: usize, interpolation: Interpolation, sample_rate: f64) -> Self {
+        ModComb {
+            delay_line: DelayLine::with_interpolation(max_delay_samples, interpolation),
+            lfo: Lfo::new(0.0, sample_rate),
+            delay: DelayCrossfade::new(1.0),
+            depth_samples: 0.0,
+            sample_rate,
+            filter: {
+                let mut filter = BandDecayFilter::new();
+                filter.set_gain(0.0);
+                filter
+            },
+            dampening: T::ZERO,
+            filter_store: T::ZERO,
+        }
+    }
+
+    /// Sets the delay at once, for use before the signal starts.
+    pub fn prepare(&mut self, delay_samples: f64) {
+        self.delay.jump(delay_samples.round());
+    }
+
+    pub fn set_delay(&mut self, delay_samples: f64) {
+        self.delay.set_target(delay_samples.round());
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.filter_store = T::ZERO;
+        self.filter.reset();
+    }
+
+    pub fn set_gain(&mut self, gain: f64) {
+        self.filter.set_gain(gain);
+    }
+
+    /// Loop gains in the low, mid and high bands, see
+    /// `BandDecayFilter::design`.
+    pub fn set_band_gains(&mut self, gains: (f64, f64, f64), crossovers_hz: (f64, f64)) {
+        self.filter.design(gains, crossovers_hz, self.sample_rate);
+    }
+
+    /// Coefficient of the one-pole low-pass in the feedback path, 0.0 leaves
+    /// the feedback unfiltered.
+    pub fn set_dampening(&mut self, dampening: f64) {
+        self.dampening = T::from_f64(dampening);
+    }
+
+    /// Sweeps the delay by up to `depth_samples` either way. A depth of 0.0
+    /// leaves the delay fixed.
+    pub fn set_modulation(&mut self, depth_samples: f64, rate_hz: f64) {
+        self.depth_samples = depth_samples;
+        self.lfo.set_freq(rate_hz);
+    }
+
+    pub fn set_lfo_phase(&mut self, phase: f64) {
+        self.lfo.set_phase(phase);
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for ModComb<T> {
+    fn process(&mut self, input: T) -> T {
+        // Read before writing, so a read of `d - 1` is `d` samples old.
+        let modulation = self.depth_samples * self.lfo.next();
+        let output = self.delay.read(|delay, head| self.delay_line.read_head(delay, head), modulation - 1.0);
+        self.filter_store = output * (T::ONE - self.dampening) + self.filter_store * self.dampening;
+        let feedback = self.filter.process(self.filter_store);
+        self.delay_line.write(input + feedback);
+        output
+    }
+}
+
+impl<T: Float> BlockProcessor<T> for ModComb<T> {
+    fn process_block_in_place(&mut self, buffer: &mut [T]) {
+        // Without a crossfade the read position only moves with the LFO.
+        let Some(delay_samples) = self.delay.steady_delay() else {
+            buffer.iter_mut().for_each(|sample| *sample = self.process(*sample));
+            return;
+        };
+        let (dampening, depth_samples, head) = (self.dampening, self.depth_samples, self.delay.head());
+        let mut filter_store = self.filter_store;
+        for sample in buffer.iter_mut() {
+            let modulation = depth_samples * self.lfo.next();
+            let output = self.delay_line.read_head(delay_samples + (modulation - 1.0), head);
+            filter_store = output * (T::ONE - dampening) + filter_store * dampening;
+            let feedback = self.filter.process(filter_store);
+            self.delay_line.write(*sample + feedback);
+            *sample = output;
+        }
+        self.filter_store = filter_store;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modulation_moves_echoes<T: Float>() {
+        let impulse = |n: usize| if n == 0 { T::ONE } else { T::ZERO };
+        let mut uut = ModComb::<T>::new(200, Interpolation::Linear, 44100.0);
+        uut.prepare(100.0);
+        uut.set_gain(0.5);
+        let out: Vec<f64> = (0..301).map(|n| uut.process(impulse(n)).to_f64()).collect();
+        assert_eq!((out[100], out[200], out[300]), (1.0, 0.5, 0.25));
+        assert_eq!(out.iter().sum::<f64>(), 1.75);
+
+        let mut uut = ModComb::<T>::new(200, Interpolation::Linear, 44100.0);
+        uut.prepare(100.0);
+        uut.set_gain(0.5);
+        uut.set_modulation(10.0, 100.0);
+        let out: Vec<f64> = (0..301).map(|n| uut.process(impulse(n)).to_f64()).collect();
+        // The LFO is rising past its peak when the echo comes round, so the
+        // echo arrives late.
+        assert_eq!(out[100], 0.0);
+        assert!(out[105..115].iter().sum::<f64>() > 0.9);
+    }
+
+    #[test]
+    fn test_modulation_moves_echoes() {
+        modulation_moves_echoes::<f32>();
+        modulation_moves_echoes::<f64>();
+    }
+}
@@ -0,0 +1,186 @@
+use euterpe_rs::processor::AudioProcessor;
+use crate::all_pass::AllPass;
+use crate::delay_line::{DelayLine, Interpolation};
+use crate::float::Float;
+use crate::mod_comb::ModComb;
+use crate::reverb::{
+    get_band_gains_from_rt60, get_length_in_samples, Reverb, DEFAULT_HIGH_CROSSOVER_HZ, DEFAULT_LOW_CROSSOVER_HZ,
+};
+
+const NUM_COMBS: usize = 6;
+const NUM_ER_TAPS: usize = 18;
+const COMB_DELAYS_MS: [f64; NUM_COMBS] = [50.0, 56.0, 61.0, 68.0, 72.0, 78.0];
+const APF_DELAY_MS: f64 = 6.0;
+const APF_GAIN: f64 = 0.7;
+const COMB_MAX_DELAY_MS: f64 = 80.0;
+const APF_MAX_DELAY_MS: f64 = 10.0;
+/// Tap times and gains of the early reflections Moorer measured in Boston
+/// Symphony Hall.
+const ER_TAPS: [(f64, f64); NUM_ER_TAPS] = [
+    (4.3, 0.841), (21.5, 0.504), (22.5, 0.491), (26.8, 0.379), (27.0, 0.380), (29.8, 0.346),
+    (45.8, 0.289), (48.5, 0.272), (57.2, 0.192), (58.7, 0.193), (59.5, 0.217), (61.2, 0.181),
+    (70.7, 0.180), (70.8, 0.181), (72.6, 0.176), (74.1, 0.142), (75.3, 0.167), (79.7, 0.134),
+];
+const ER_MAX_DELAY_MS: f64 = 80.0;
+
+/// Moorer's variant of the Schroeder reverb: a tapped delay line produces the
+/// early reflections, which then feed a bank of low-pass combs and an all-pass.
+pub struct Moorer<T: Float = f64> {
+    early_reflections: DelayLine<T>,
+    combs: [(ModComb<T>, f64); NUM_COMBS],
+    all_pass: AllPass<T>,
+    sample_rate: f64,
+    rt60_ms: f64,
+    delay_offset_ms: f64,
+    dampening: f64,
+    low_multiplier: f64,
+    high_multiplier: f64,
+    crossovers_hz: (f64, f64),
+}
+
+impl<T: Float> Moorer<T> {
+    pub fn new(sample_rate: f64) -> Self {
+        let comb_delay_length = get_length_in_samples(COMB_MAX_DELAY_MS, sample_rate) as usize;
+        let apf_delay_length = get_length_in_samples(APF_MAX_DELAY_MS, sample_rate) as usize;
+        let er_delay_length = get_length_in_samples(ER_MAX_DELAY_MS, sample_rate).ceil() as usize;
+
+        Moorer {
+            early_reflections: DelayLine::new(er_delay_length),
+            combs: COMB_DELAYS_MS.map(|delay_ms| (ModComb::new(comb_delay_length, Interpolation::Linear, sample_rate), delay_ms)),
+            all_pass: AllPass::new(apf_delay_length),
+            sample_rate,
+            rt60_ms: 0.0,
+            delay_offset_ms: 0.0,
+            dampening: 0.0,
+            low_multiplier: 1.0,
+            high_multiplier: 1.0,
+            crossovers_hz: (DEFAULT_LOW_CROSSOVER_HZ, DEFAULT_HIGH_CROSSOVER_HZ),
+        }
+    }
+
+    /// Designs each comb's loop filter so its bands decay at their RT60s.
+    fn update_feedback(&mut self) {
+        let multipliers = (self.low_multiplier, self.high_multiplier);
+        for (comb, delay_ms) in self.combs.iter_mut() {
+            let gains = get_band_gains_from_rt60(*delay_ms + self.delay_offset_ms, self.rt60_ms, multipliers);
+            comb.set_band_gains(gains, self.crossovers_hz);
+        }
+    }
+
+    fn early_reflections_gain() -> f64 {
+        1.0 / ER_TAPS.iter().map(|(_, gain)| gain).sum::<f64>()
+    }
+}
+
+impl<T: Float> Reverb<T> for Moorer<T> {
+    fn prepare(&mut self, sample_rate: f64, rt60_ms: f64) {
+        self.sample_rate = sample_rate;
+        self.rt60_ms = rt60_ms;
+
+        for (comb, delay_ms) in self.combs.iter_mut() {
+            comb.prepare(get_length_in_samples(*delay_ms + self.delay_offset_ms, sample_rate));
+        }
+        self.update_feedback();
+
+        let delay_samples = get_length_in_samples(APF_DELAY_MS + self.delay_offset_ms, sample_rate);
+        self.all_pass.prepare(delay_samples, APF_GAIN);
+    }
+
+    fn update_reverb_time(&mut self, rt60_ms: f64) {
+        if rt60_ms == self.rt60_ms {
+            return;
+        }
+        self.rt60_ms = rt60_ms;
+        self.update_feedback();
+    }
+
+    fn set_dampening(&mut self, dampening: f64) {
+        if dampening == self.dampening {
+            return;
+        }
+        self.dampening = dampening;
+        for (comb, _) in self.combs.iter_mut() {
+            comb.set_dampening(dampening);
+        }
+    }
+
+    fn set_band_decay(&mut self, low_multiplier: f64, high_multiplier: f64) {
+        let high_multiplier = high_multiplier.min(1.0);
+        if (low_multiplier, high_multiplier) == (self.low_multiplier, self.high_multiplier) {
+            return;
+        }
+        self.low_multiplier = low_multiplier;
+        self.high_multiplier = high_multiplier;
+        self.update_feedback();
+    }
+
+    fn set_crossovers(&mut self, low_hz: f64, high_hz: f64) {
+        if (low_hz, high_hz) == self.crossovers_hz {
+            return;
+        }
+        self.crossovers_hz = (low_hz, high_hz);
+        self.update_feedback();
+    }
+
+    fn set_delay_offset(&mut self, offset_ms: f64) {
+        if offset_ms == self.delay_offset_ms {
+            return;
+        }
+        self.delay_offset_ms = offset_ms;
+        for (comb, delay_ms) in self.combs.iter_mut() {
+            comb.set_delay(get_length_in_samples(*delay_ms + offset_ms, self.sample_rate));
+        }
+        self.update_feedback();
+        self.all_pass.set_delay(get_length_in_samples(APF_DELAY_MS + offset_ms, self.sample_rate));
+    }
+
+    fn max_delay_ms(&self) -> f64 {
+        // The combs are fed from the early-reflection taps.
+        ER_MAX_DELAY_MS + COMB_MAX_DELAY_MS
+    }
+
+    fn reset(&mut self) {
+        self.early_reflections.reset();
+        for (comb, _) in self.combs.iter_mut() {
+            comb.reset();
+        }
+        self.all_pass.reset();
+    }
+
+    fn process_wet(&mut self, input: T) -> T {
+        self.process(input)
+    }
+}
+
+impl<T: Float> AudioProcessor<T> for Moorer<T> {
+    fn process(&mut self, input: T) -> T {
+        self.early_reflections.write(input);
+        let early = ER_TAPS.iter().fold(T::ZERO, |early, (delay_ms, gain)| {
+            early + T::from_f64(*gain) * self.early_reflections.read(get_length_in_samples(*delay_ms, self.sample_rate))
+        }) * T::from_f64(Self::early_reflections_gain());
+
+        let mut late = T::ZERO;
+        for (comb, _) in self.combs.iter_mut() {
+            late += comb.process(early);
+        }
+        late = self.all_pass.process(late / T::from_f64(NUM_COMBS as f64));
+
+        early + late
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_impulse_response_decays;
+
+    #[test]
+    fn test_impulse_response_decays() {
+        let sample_rate = 44100.0;
+        let mut uut = Moorer::<f64>::new(sample_rate);
+        uut.prepare(sample_rate, 1000.0);
+        uut.set_dampening(0.5);
+
+        assert_impulse_response_decays(&mut uut);
+    }
+}
@@ -1,45 +1,141 @@
 use nih_plug::prelude::*;
 use nih_plug_vizia::ViziaState;
 use std::sync::Arc;
-use euterpe_rs::processor::AudioProcessor;
-use crate::schroeder::Schroeder;
+use crate::block::MAX_BLOCK_SIZE;
+use crate::early_reflections::Room;
+use crate::float::Float;
+use crate::lfo::Waveform;
+use crate::reverb::Algorithm;
+use crate::stereo::StereoReverb;
+use crate::tempo::{NoteDivision, PreDelayDivision};
 use crate::editor;
 
 pub struct SchroederPlugin {
     params: Arc<SchroederParams>,
-    processor: Schroeder,
+    processor: StereoReverb<f32>,
     sample_rate: f32,
+    silent_samples: u32,
+    /// Whether the sidechain bus has carried signal since the last reset.
+    sidechain_heard: bool,
 }
 
 const DEFAULT_SAMPLE_RATE : f32 = 44100.0;
+/// Level below which input and output count as silence (about -100 dBFS).
+const SILENCE_THRESHOLD : f32 = 1e-5;
 
 #[derive(Params)]
 pub(crate) struct SchroederParams {
     #[persist = "editor-state"]
     editor_state: Arc<ViziaState>,
 
+    #[id = "algorithm"]
+    pub algorithm: EnumParam<Algorithm>,
+
     #[id = "rt60"]
     pub rt60: FloatParam,
 
+    #[id = "preDelay"]
+    pub pre_delay: FloatParam,
+
+    #[id = "preDelaySync"]
+    pub pre_delay_sync: BoolParam,
+
+    #[id = "preDelayDivision"]
+    pub pre_delay_division: EnumParam<PreDelayDivision>,
+
+    #[id = "room"]
+    pub room: EnumParam<Room>,
+
+    #[id = "erBalance"]
+    pub er_balance: FloatParam,
+
+    #[id = "diffusion"]
+    pub diffusion: FloatParam,
+
     #[id = "dampening"]
     pub dampening: FloatParam,
 
+    #[id = "lowDecay"]
+    pub low_decay: FloatParam,
+
+    #[id = "highDecay"]
+    pub high_decay: FloatParam,
+
+    #[id = "lowCrossover"]
+    pub low_crossover: FloatParam,
+
+    #[id = "highCrossover"]
+    pub high_crossover: FloatParam,
+
+    #[id = "lowCut"]
+    pub low_cut: FloatParam,
+
+    #[id = "highCut"]
+    pub high_cut: FloatParam,
+
+    #[id = "tilt"]
+    pub tilt: FloatParam,
+
     #[id = "dryWetMix"]
     pub dry_wet_mix: FloatParam,
 
+    #[id = "combModDepth"]
+    pub comb_mod_depth: FloatParam,
+
+    #[id = "combModRate"]
+    pub comb_mod_rate: FloatParam,
+
+    #[id = "freeze"]
+    pub freeze: BoolParam,
+
+    #[id = "size"]
+    pub size: FloatParam,
+
     #[id = "modFreq"]
     pub mod_freq: FloatParam,
 
+    #[id = "modDepth"]
+    pub mod_depth: FloatParam,
+
+    #[id = "modWaveform"]
+    pub mod_waveform: EnumParam<Waveform>,
+
     #[id = "modEnabled"]
     pub mod_enabled: BoolParam,
+
+    #[id = "modSync"]
+    pub mod_sync: BoolParam,
+
+    #[id = "modDivision"]
+    pub mod_division: EnumParam<NoteDivision>,
+
+    #[id = "duckAmount"]
+    pub duck_amount: FloatParam,
+
+    #[id = "duckAttack"]
+    pub duck_attack: FloatParam,
+
+    #[id = "duckRelease"]
+    pub duck_release: FloatParam,
+
+    #[id = "duckSidechain"]
+    pub duck_sidechain: BoolParam,
+
+    #[id = "stereoSpread"]
+    pub stereo_spread: FloatParam,
+
+    #[id = "width"]
+    pub width: FloatParam,
 }
 
 impl Default for SchroederPlugin {
     fn default() -> Self {
         Self {
             params: Arc::new(SchroederParams::default()),
-            processor: Schroeder::new(DEFAULT_SAMPLE_RATE as f64),
-            sample_rate: DEFAULT_SAMPLE_RATE as f32,
+            processor: StereoReverb::new(DEFAULT_SAMPLE_RATE as f64),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            silent_samples: 0,
+            sidechain_heard: false,
         }
     }
 }
@@ -48,6 +144,8 @@ impl Default for SchroederParams{
     fn default() -> Self {
         Self {
             editor_state: editor::default_state(),
+            algorithm : EnumParam::new("Algorithm", Algorithm::Schroeder),
+
             rt60 : FloatParam::new(
                 "Rev. Time",
                 2.0,
@@ -56,6 +154,34 @@ impl Default for SchroederParams{
                 .with_unit(" s")
                 .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
+            pre_delay : FloatParam::new(
+                "Pre-Delay",
+                0.0,
+                FloatRange::Linear{min : 0.0, max : 250.0})
+                .with_smoother(SmoothingStyle::Linear(50.0))
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            pre_delay_sync : BoolParam::new("Pre-Delay Sync", false),
+            pre_delay_division : EnumParam::new("Pre-Delay Note", PreDelayDivision::Sixteenth),
+
+            room : EnumParam::new("Room", Room::Hall),
+
+            er_balance : FloatParam::new(
+                "ER/Late",
+                0.3,
+                FloatRange::Linear{min : 0.0, max : 1.0}
+                ).with_smoother(SmoothingStyle::Linear(3.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0)),
+
+            diffusion : FloatParam::new(
+                "Diffusion",
+                1.0,
+                FloatRange::Linear{min : 0.0, max : 1.0}
+                ).with_smoother(SmoothingStyle::Linear(3.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0)),
+
             dampening : FloatParam::new(
                 "Dampening",
                 0.5,
@@ -63,6 +189,60 @@ impl Default for SchroederParams{
                 ).with_smoother(SmoothingStyle::Linear(3.0))
                 .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
+            low_decay : FloatParam::new(
+                "Low Decay",
+                1.0,
+                FloatRange::Linear{min : 0.5, max : 2.0}
+                ).with_smoother(SmoothingStyle::Linear(3.0))
+                .with_unit("x")
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            // The high band may only decay as fast as or faster than the mids,
+            // a longer high decay would make the loop filters ring.
+            high_decay : FloatParam::new(
+                "High Decay",
+                1.0,
+                FloatRange::Linear{min : 0.1, max : 1.0}
+                ).with_smoother(SmoothingStyle::Linear(3.0))
+                .with_unit("x")
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            low_crossover : FloatParam::new(
+                "Low X-over",
+                250.0,
+                FloatRange::Skewed{min : 50.0, max : 1000.0, factor : FloatRange::skew_factor(-1.0)}
+                ).with_smoother(SmoothingStyle::Logarithmic(50.0))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(0)),
+
+            high_crossover : FloatParam::new(
+                "High X-over",
+                4000.0,
+                FloatRange::Skewed{min : 1000.0, max : 10000.0, factor : FloatRange::skew_factor(-1.0)}
+                ).with_smoother(SmoothingStyle::Logarithmic(50.0))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(1)),
+
+            low_cut : FloatParam::new(
+                "Low Cut",
+                80.0,
+                FloatRange::Skewed{min : 20.0, max : 2000.0, factor : FloatRange::skew_factor(-2.0)}
+                ).with_smoother(SmoothingStyle::Logarithmic(50.0))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(0)),
+
+            high_cut : FloatParam::new(
+                "High Cut",
+                20000.0,
+                FloatRange::Skewed{min : 1000.0, max : 20000.0, factor : FloatRange::skew_factor(-1.0)}
+                ).with_smoother(SmoothingStyle::Logarithmic(50.0))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(1)),
+
+            tilt : FloatParam::new(
+                "Tilt",
+                0.0,
+                FloatRange::Linear{min : -6.0, max : 6.0}
+                ).with_smoother(SmoothingStyle::Linear(50.0))
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
             dry_wet_mix : FloatParam::new(
                 "D/W",
                 0.5,
@@ -71,6 +251,32 @@ impl Default for SchroederParams{
                 .with_unit("%")
                 .with_value_to_string(formatters::v2s_f32_percentage(2)),
 
+            comb_mod_depth : FloatParam::new(
+                "Mod Depth",
+                0.0,
+                FloatRange::Linear{min : 0.0, max : 2.0}
+                   ).with_smoother(SmoothingStyle::Linear(50.0))
+                   .with_unit(" ms")
+                   .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            comb_mod_rate : FloatParam::new(
+                "Mod Rate",
+                0.5,
+                FloatRange::Skewed{min : 0.05, max : 5.0, factor : FloatRange::skew_factor(-1.0)}
+                   ).with_smoother(SmoothingStyle::Logarithmic(50.0))
+                   .with_unit(" Hz")
+                   .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            freeze : BoolParam::new("Freeze", false),
+
+            size : FloatParam::new(
+                "Size",
+                1.0,
+                FloatRange::Skewed{min : 0.25, max : 2.0, factor : FloatRange::skew_factor(-1.0)}
+                   ).with_smoother(SmoothingStyle::Logarithmic(50.0))
+                   .with_unit("x")
+                   .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
             mod_freq : FloatParam::new(
                 "Lfo Freq",
                 0.5,
@@ -78,7 +284,56 @@ impl Default for SchroederParams{
                    ).with_smoother(SmoothingStyle::Exponential(10.0))
                    .with_unit(" Hz")
                    .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            mod_depth : FloatParam::new(
+                "Lfo Depth",
+                1.0,
+                FloatRange::Linear{min : 0.0, max : 10.0}
+                   ).with_smoother(SmoothingStyle::Linear(50.0))
+                   .with_unit(" ms")
+                   .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            mod_waveform : EnumParam::new("Lfo Shape", Waveform::Triangle),
             mod_enabled : BoolParam::new("Lfo", false),
+            mod_sync : BoolParam::new("Lfo Sync", false),
+            mod_division : EnumParam::new("Lfo Note", NoteDivision::Whole),
+
+            duck_amount : FloatParam::new(
+                "Duck",
+                0.0,
+                FloatRange::Linear{min : 0.0, max : 1.0}
+                   ).with_smoother(SmoothingStyle::Linear(3.0))
+                   .with_unit("%")
+                   .with_value_to_string(formatters::v2s_f32_percentage(0)),
+
+            duck_attack : FloatParam::new(
+                "Duck Attack",
+                10.0,
+                FloatRange::Skewed{min : 1.0, max : 100.0, factor : FloatRange::skew_factor(-1.0)}
+                   ).with_unit(" ms")
+                   .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            duck_release : FloatParam::new(
+                "Duck Release",
+                250.0,
+                FloatRange::Skewed{min : 10.0, max : 2000.0, factor : FloatRange::skew_factor(-1.0)}
+                   ).with_unit(" ms")
+                   .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            duck_sidechain : BoolParam::new("Duck Sidechain", false),
+
+            stereo_spread : FloatParam::new(
+                "Spread",
+                0.5,
+                FloatRange::Linear{min : 0.0, max : 1.0}
+                   ).with_smoother(SmoothingStyle::Linear(50.0))
+                   .with_unit("%")
+                   .with_value_to_string(formatters::v2s_f32_percentage(0)),
+
+            width : FloatParam::new(
+                "Width",
+                1.0,
+                FloatRange::Linear{min : 0.0, max : 2.0}
+                   ).with_smoother(SmoothingStyle::Linear(3.0))
+                   .with_unit("%")
+                   .with_value_to_string(formatters::v2s_f32_percentage(0)),
         }
     }
 }
@@ -91,11 +346,23 @@ impl Plugin for SchroederPlugin {
     const EMAIL : &'static str = "mirroraudio@gmail.com";
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: NonZeroU32::new(2),
-        main_output_channels: NonZeroU32::new(2),
-        ..AudioIOLayout::const_default()
-    }];
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        },
+    ];
 
     type SysExMessage = ();
     type BackgroundTask = ();
@@ -111,14 +378,45 @@ impl Plugin for SchroederPlugin {
             _context: &mut impl InitContext<Self>,
         ) -> bool {
         self.sample_rate = buffer_config.sample_rate;
-        self.processor.prepare(self.sample_rate as f64, (self.params.rt60.default_plain_value() * 1000.0) as f64);
-        self.processor.set_dampening(0.5);
-        self.processor.set_dry_wet_mix(0.5);
-        self.processor.set_mod_enabled(false);
-        self.processor.set_mod_lfo_freq(0.5);
+        // Delay buffers and the modulator's LFO are sized for a sample rate at
+        // construction, so the whole network is rebuilt for the host's rate.
+        self.processor = StereoReverb::new(self.sample_rate as f64);
+        self.processor.prepare(self.sample_rate as f64, (self.params.rt60.value() * 1000.0) as f64);
+        self.processor.set_algorithm(self.params.algorithm.value());
+        self.processor.set_pre_delay(self.params.pre_delay.value() as f64);
+        self.processor.set_room(self.params.room.value());
+        self.processor.set_er_balance(self.params.er_balance.value() as f64);
+        self.processor.set_diffusion(self.params.diffusion.value() as f64);
+        self.processor.set_dampening(self.params.dampening.value() as f64);
+        self.processor.set_band_decay(self.params.low_decay.value() as f64, self.params.high_decay.value() as f64);
+        self.processor.set_crossovers(self.params.low_crossover.value() as f64, self.params.high_crossover.value() as f64);
+        self.processor.set_low_cut(self.params.low_cut.value() as f64);
+        self.processor.set_high_cut(self.params.high_cut.value() as f64);
+        self.processor.set_tilt(self.params.tilt.value() as f64);
+        self.processor.set_dry_wet_mix(self.params.dry_wet_mix.value() as f64);
+        self.processor.set_mod_enabled(self.params.mod_enabled.value());
+        self.processor.set_freeze(self.params.freeze.value());
+        self.processor.set_size(self.params.size.value() as f64);
+        self.processor.set_comb_modulation(self.params.comb_mod_depth.value() as f64, self.params.comb_mod_rate.value() as f64);
+        self.processor.set_mod_lfo_freq(self.params.mod_freq.value());
+        self.processor.set_mod_depth(self.params.mod_depth.value() as f64);
+        self.processor.set_mod_waveform(self.params.mod_waveform.value());
+        self.processor.set_stereo_spread(self.params.stereo_spread.value() as f64);
+        self.processor.set_width(self.params.width.value() as f64);
+        self.processor.set_ducking(
+            self.params.duck_amount.value() as f64,
+            self.params.duck_attack.value() as f64,
+            self.params.duck_release.value() as f64,
+        );
         true
     }
 
+    fn reset(&mut self) {
+        self.processor.reset();
+        self.silent_samples = 0;
+        self.sidechain_heard = false;
+    }
+
     fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         editor::create(self.params.clone(), self.params.editor_state.clone())
     }
@@ -126,37 +424,236 @@ impl Plugin for SchroederPlugin {
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux : &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        aux : &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
         
-        self.processor.update_reverb_time((self.params.rt60.smoothed.next() * 1000.0) as f64); 
-        self.processor.set_dampening(self.params.dampening.smoothed.next() as f64);
-        self.processor.set_dry_wet_mix(self.params.dry_wet_mix.smoothed.next() as f64);        
+        let transport = context.transport();
+        let sync = TempoSync::new(&self.params, transport.tempo);
+        if let Some(phase) = self.synced_lfo_phase(transport) {
+            self.processor.set_mod_lfo_phase(phase);
+        }
+
+        self.processor.set_algorithm(self.params.algorithm.value());
+        self.processor.set_room(self.params.room.value());
         self.processor.set_mod_enabled(self.params.mod_enabled.value());
-        self.processor.set_mod_lfo_freq(self.params.mod_freq.smoothed.next() as f32);
+        self.processor.set_mod_waveform(self.params.mod_waveform.value());
+        self.processor.set_freeze(self.params.freeze.value());
 
-        for mut channel_samples in buffer.iter_samples() {
-            
-            let mut mono_sample : f32 = 0.0;
-            let num_channels = channel_samples.len();
+        // Hosts pass an unrouted sidechain bus as silence, so the dry input
+        // keys the ducker until the sidechain has carried signal.
+        let sidechain = match aux.inputs.first() {
+            Some(sidechain) if self.params.duck_sidechain.value() => {
+                let sidechain = sidechain.as_slice_immutable();
+                self.sidechain_heard |= sidechain
+                    .iter()
+                    .any(|channel| channel.iter().any(|sample| sample.abs() >= SILENCE_THRESHOLD));
+                self.sidechain_heard.then_some(sidechain)
+            }
+            _ => None,
+        };
 
-            for sample in channel_samples.iter_mut(){
-                mono_sample += *sample;
+        // Both layouts are stereo, so the host always passes two channels.
+        let [left, right] = buffer.as_slice() else {
+            return ProcessStatus::Error("expected a stereo buffer");
+        };
+        let blocks = left.chunks_mut(MAX_BLOCK_SIZE).zip(right.chunks_mut(MAX_BLOCK_SIZE));
+        for (block_index, (left, right)) in blocks.enumerate() {
+            let (block_start, num_samples) = (block_index * MAX_BLOCK_SIZE, left.len());
+            // The block is processed in place, the silence check needs the
+            // dry input as well.
+            let mut dry = [[0.0; MAX_BLOCK_SIZE]; 2];
+            dry[0][..num_samples].copy_from_slice(left);
+            dry[1][..num_samples].copy_from_slice(right);
+            let mut key = [0.0; MAX_BLOCK_SIZE];
+            for (sample_index, key) in key[..num_samples].iter_mut().enumerate() {
+                *key = match sidechain {
+                    Some(sidechain) => sidechain
+                        .iter()
+                        .map(|channel| channel[block_start + sample_index].abs())
+                        .fold(0.0, f32::max),
+                    None => dry[0][sample_index].abs().max(dry[1][sample_index].abs()),
+                };
             }
 
-            mono_sample /= num_channels as f32;
+            process_block(&self.params, &sync, &mut self.processor, left, right, &key[..num_samples]);
+            self.track_silence([&dry[0][..num_samples], &dry[1][..num_samples]], [left, right]);
+        }
 
-            let output = self.processor.process(mono_sample as f64) as f32;
+        self.process_status()
+    }
 
-            for sample in channel_samples.iter_mut(){
-                *sample = output;
+}
+
+impl SchroederPlugin {
+    /// Counts how long input and output have both stayed below
+    /// `SILENCE_THRESHOLD`.
+    fn track_silence(&mut self, dry: [&[f32]; 2], wet: [&[f32]; 2]) {
+        for sample_index in 0..dry[0].len() {
+            let peak = dry[0][sample_index].abs().max(dry[1][sample_index].abs())
+                .max(wet[0][sample_index].abs()).max(wet[1][sample_index].abs());
+            if peak < SILENCE_THRESHOLD {
+                self.silent_samples = self.silent_samples.saturating_add(1);
+            } else {
+                self.silent_samples = 0;
             }
-        } 
+        }
+    }
+
+    fn process_status(&self) -> ProcessStatus {
+        // Silence shorter than the longest gap before the wet signal arrives
+        // may just be the pre-delay or the wait for the first echo.
+        let silence_hold = self.processor.max_silent_gap_ms() / 1000.0 * self.sample_rate as f64;
+        if self.processor.is_frozen() {
+            // A frozen tail never ends.
+            ProcessStatus::KeepAlive
+        } else if self.silent_samples as f64 >= silence_hold {
+            // The tail has died out, the host is free to suspend us.
+            ProcessStatus::Normal
+        } else {
+            ProcessStatus::Tail(self.tail_length_samples())
+        }
+    }
+
+    /// Samples it takes the tail to decay by 60 dB in its slowest band,
+    /// counted from the end of the input.
+    fn tail_length_samples(&self) -> u32 {
+        (self.processor.tail_length_ms() / 1000.0 * self.sample_rate as f64).ceil() as u32
+    }
+
+    /// Phase the synced LFO should have at the start of this block. `None`
+    /// when the LFO runs free or the host is stopped or does not report its
+    /// position.
+    fn synced_lfo_phase(&self, transport: &Transport) -> Option<f64> {
+        if !self.params.mod_sync.value() || !transport.playing {
+            return None;
+        }
+        Some(lfo_phase_at(transport.pos_beats()?, self.params.mod_division.value().beats()))
+    }
+}
+
+/// Phase of an LFO that runs one cycle per `division_beats` and started at
+/// beat 0. Counting from the song start rather than the bar keeps the phase
+/// continuous across bar lines for divisions that do not fit the bar evenly.
+fn lfo_phase_at(pos_beats: f64, division_beats: f64) -> f64 {
+    pos_beats.rem_euclid(division_beats) / division_beats
+}
+
+/// Tempo-derived values that replace the LFO rate and the pre-delay while
+/// their sync switch is on and the host reports a tempo.
+#[derive(Default)]
+struct TempoSync {
+    lfo_freq_hz: Option<f32>,
+    pre_delay_ms: Option<f32>,
+}
+
+impl TempoSync {
+    fn new(params: &SchroederParams, tempo_bpm: Option<f64>) -> Self {
+        let Some(tempo_bpm) = tempo_bpm else {
+            return TempoSync::default();
+        };
+        TempoSync {
+            lfo_freq_hz: params.mod_sync.value().then(|| params.mod_division.value().freq_hz(tempo_bpm) as f32),
+            pre_delay_ms: params.pre_delay_sync.value().then(|| params.pre_delay_division.value().note().length_ms(tempo_bpm) as f32),
+        }
+    }
+}
 
-        ProcessStatus::Normal
+/// Runs one block of up to `MAX_BLOCK_SIZE` samples through the processor.
+/// While any parameter is still gliding its smoothers are advanced sample by
+/// sample; once they have all settled the block path takes over. Either way
+/// the output is the same, so it does not depend on the host's buffer size.
+fn process_block<T: Float>(
+    params: &SchroederParams,
+    sync: &TempoSync,
+    processor: &mut StereoReverb<T>,
+    left: &mut [T],
+    right: &mut [T],
+    key: &[T],
+) {
+    if !is_smoothing(params) {
+        update_smoothed_params(params, sync, processor);
+        processor.process_block(left, right, key);
+        return;
+    }
+    for sample_index in 0..left.len() {
+        update_smoothed_params(params, sync, processor);
+        (left[sample_index], right[sample_index]) =
+            processor.process_with_key(left[sample_index], right[sample_index], key[sample_index]);
     }
+}
+
+/// Whether any of the parameters `update_smoothed_params` reads is still
+/// moving towards its target.
+fn is_smoothing(params: &SchroederParams) -> bool {
+    [
+        &params.rt60,
+        &params.pre_delay,
+        &params.er_balance,
+        &params.diffusion,
+        &params.dampening,
+        &params.low_decay,
+        &params.high_decay,
+        &params.low_crossover,
+        &params.high_crossover,
+        &params.low_cut,
+        &params.high_cut,
+        &params.tilt,
+        &params.dry_wet_mix,
+        &params.comb_mod_depth,
+        &params.comb_mod_rate,
+        &params.size,
+        &params.mod_freq,
+        &params.mod_depth,
+        &params.stereo_spread,
+        &params.width,
+        &params.duck_amount,
+    ]
+    .iter()
+    .any(|param| param.smoothed.is_smoothing())
+}
 
+/// Advances every smoother by one sample and hands the values to the processor,
+/// so automation ramps come out the same whatever the host's buffer size is.
+/// Synced parameters still advance their smoothers, so switching sync off
+/// picks up from where the knob is.
+fn update_smoothed_params<T: Float>(params: &SchroederParams, sync: &TempoSync, processor: &mut StereoReverb<T>) {
+    processor.update_reverb_time((params.rt60.smoothed.next() * 1000.0) as f64);
+    let pre_delay_ms = params.pre_delay.smoothed.next();
+    processor.set_pre_delay(sync.pre_delay_ms.unwrap_or(pre_delay_ms) as f64);
+    processor.set_er_balance(params.er_balance.smoothed.next() as f64);
+    processor.set_diffusion(params.diffusion.smoothed.next() as f64);
+    processor.set_dampening(params.dampening.smoothed.next() as f64);
+    processor.set_band_decay(params.low_decay.smoothed.next() as f64, params.high_decay.smoothed.next() as f64);
+    processor.set_crossovers(params.low_crossover.smoothed.next() as f64, params.high_crossover.smoothed.next() as f64);
+    processor.set_low_cut(params.low_cut.smoothed.next() as f64);
+    processor.set_high_cut(params.high_cut.smoothed.next() as f64);
+    processor.set_tilt(params.tilt.smoothed.next() as f64);
+    processor.set_dry_wet_mix(params.dry_wet_mix.smoothed.next() as f64);
+    processor.set_comb_modulation(params.comb_mod_depth.smoothed.next() as f64, params.comb_mod_rate.smoothed.next() as f64);
+    processor.set_size(params.size.smoothed.next() as f64);
+    let mod_freq = params.mod_freq.smoothed.next();
+    processor.set_mod_lfo_freq(sync.lfo_freq_hz.unwrap_or(mod_freq));
+    processor.set_mod_depth(params.mod_depth.smoothed.next() as f64);
+    processor.set_stereo_spread(params.stereo_spread.smoothed.next() as f64);
+    processor.set_width(params.width.smoothed.next() as f64);
+    processor.set_ducking(
+        params.duck_amount.smoothed.next() as f64,
+        params.duck_attack.value() as f64,
+        params.duck_release.value() as f64,
+    );
+}
+
+impl ClapPlugin for SchroederPlugin {
+    const CLAP_ID: &'static str = "com.mirroraudio.schroederverb";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Classic Schroeder reverb");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Reverb,
+        ClapFeature::Stereo
+    ];
 }
 
 impl Vst3Plugin for SchroederPlugin {
@@ -168,4 +665,217 @@ impl Vst3Plugin for SchroederPlugin {
     ];
 }
 
+nih_export_clap!(SchroederPlugin);
 nih_export_vst3!(SchroederPlugin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::noise;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+    const SWEEP_STEP: usize = 8192;
+
+    /// Every smoothed parameter with the two values the sweep moves it
+    /// between, already reset to its default.
+    fn sweep(params: &SchroederParams) -> Vec<(&FloatParam, f32, f32)> {
+        let sweep = vec![
+            (&params.rt60, 1.0, 10.0),
+            (&params.pre_delay, 0.0, 100.0),
+            (&params.er_balance, 0.0, 1.0),
+            (&params.diffusion, 0.0, 1.0),
+            (&params.dampening, 0.0, 0.9),
+            (&params.low_decay, 0.5, 2.0),
+            (&params.high_decay, 0.2, 1.0),
+            (&params.low_crossover, 100.0, 800.0),
+            (&params.high_crossover, 2000.0, 8000.0),
+            (&params.low_cut, 20.0, 500.0),
+            (&params.high_cut, 3000.0, 20000.0),
+            (&params.tilt, -6.0, 6.0),
+            (&params.dry_wet_mix, 0.2, 1.0),
+            (&params.comb_mod_depth, 0.0, 2.0),
+            (&params.comb_mod_rate, 0.1, 5.0),
+            (&params.size, 0.25, 2.0),
+            (&params.mod_freq, 0.1, 2.0),
+            (&params.mod_depth, 0.0, 10.0),
+            (&params.stereo_spread, 0.0, 1.0),
+            (&params.width, 0.0, 2.0),
+            (&params.duck_amount, 0.0, 1.0),
+        ];
+        for (param, _, _) in sweep.iter() {
+            param.smoothed.reset(param.value());
+        }
+        sweep
+    }
+
+    /// Moves every target to the other end of its range at the start of each
+    /// `SWEEP_STEP`, like host automation.
+    fn update_targets(sweep: &[(&FloatParam, f32, f32)], sample_index: usize) {
+        if !sample_index.is_multiple_of(SWEEP_STEP) {
+            return;
+        }
+        let step = sample_index / SWEEP_STEP;
+        for (param, low, high) in sweep.iter() {
+            let target = if step.is_multiple_of(2) { *high } else { *low };
+            param.smoothed.set_target(SAMPLE_RATE, target);
+        }
+    }
+
+    /// Renders the sweep through `process_block` in host buffers of
+    /// `block_size` samples.
+    fn render<T: Float>(block_size: usize) -> Vec<(T, T)> {
+        let params = SchroederParams::default();
+        let mut processor = StereoReverb::<T>::new(SAMPLE_RATE as f64);
+        processor.prepare(SAMPLE_RATE as f64, (params.rt60.value() * 1000.0) as f64);
+        let sweep = sweep(&params);
+
+        let mut seed: u32 = 1;
+        let mut output = Vec::new();
+        for block_start in (0..SWEEP_STEP * 4).step_by(block_size) {
+            update_targets(&sweep, block_start);
+
+            // Split up the way `iter_blocks` splits the host's buffer.
+            for sub_block_start in (0..block_size).step_by(MAX_BLOCK_SIZE) {
+                let num_samples = MAX_BLOCK_SIZE.min(block_size - sub_block_start);
+                let mut left: Vec<T> = (0..num_samples).map(|_| noise(&mut seed)).collect();
+                let mut right: Vec<T> = left.iter().map(|left| -*left).collect();
+                let key: Vec<T> = left.iter().map(|left| left.abs()).collect();
+                process_block(&params, &TempoSync::default(), &mut processor, &mut left, &mut right, &key);
+                output.extend(left.into_iter().zip(right));
+            }
+        }
+        output
+    }
+
+    /// Renders the whole sweep in one pass, advancing the smoothers and the
+    /// processor one sample at a time without any block processing.
+    fn render_reference<T: Float>() -> Vec<(T, T)> {
+        let params = SchroederParams::default();
+        let mut processor = StereoReverb::<T>::new(SAMPLE_RATE as f64);
+        processor.prepare(SAMPLE_RATE as f64, (params.rt60.value() * 1000.0) as f64);
+        let sweep = sweep(&params);
+
+        let mut seed: u32 = 1;
+        (0..SWEEP_STEP * 4)
+            .map(|sample_index| {
+                update_targets(&sweep, sample_index);
+                update_smoothed_params(&params, &TempoSync::default(), &mut processor);
+                let left: T = noise(&mut seed);
+                processor.process_with_key(left, -left, left.abs())
+            })
+            .collect()
+    }
+
+    fn output_independent_of_block_size<T: Float>() {
+        let reference = render_reference::<T>();
+        for block_size in [1, 32, 256, 1024, SWEEP_STEP] {
+            assert_eq!(render::<T>(block_size), reference, "block size {}", block_size);
+        }
+    }
+
+    #[test]
+    fn test_output_independent_of_block_size() {
+        output_independent_of_block_size::<f32>();
+        output_independent_of_block_size::<f64>();
+    }
+
+    struct TestInitContext;
+
+    impl InitContext<SchroederPlugin> for TestInitContext {
+        fn plugin_api(&self) -> PluginApi {
+            PluginApi::Clap
+        }
+
+        fn execute(&self, _task: ()) {}
+
+        fn set_latency_samples(&self, _samples: u32) {}
+
+        fn set_current_voice_capacity(&self, _capacity: u32) {}
+    }
+
+    /// Wet impulse response of a default plugin initialized at `sample_rate`.
+    fn initialized_impulse_response(sample_rate: f32) -> Vec<f64> {
+        let mut plugin = SchroederPlugin::default();
+        let buffer_config = BufferConfig {
+            sample_rate,
+            min_buffer_size: None,
+            max_buffer_size: MAX_BLOCK_SIZE as u32,
+            process_mode: ProcessMode::Realtime,
+        };
+        assert!(plugin.initialize(&SchroederPlugin::AUDIO_IO_LAYOUTS[0], &buffer_config, &mut TestInitContext));
+        assert_eq!(plugin.sample_rate, sample_rate);
+
+        plugin.processor.set_dry_wet_mix(1.0);
+        let num_samples = (plugin.params.rt60.value() * sample_rate) as usize;
+        (0..num_samples)
+            .map(|n| {
+                let input = if n == 0 { 1.0 } else { 0.0 };
+                plugin.processor.process(input, input).0 as f64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_initialize_rebuilds_for_host_sample_rate() {
+        let onset_s = |impulse_response: &[f64], sample_rate: f32| {
+            impulse_response.iter().position(|x| x.abs() > 1e-9).unwrap() as f64 / sample_rate as f64
+        };
+        let reference = initialized_impulse_response(SAMPLE_RATE);
+        let impulse_response = initialized_impulse_response(96000.0);
+
+        // Every delay, down to the early-reflection taps, is laid out in time
+        // rather than in samples at the default rate.
+        let (reference_onset_s, wet_onset_s) = (onset_s(&reference, SAMPLE_RATE), onset_s(&impulse_response, 96000.0));
+        assert!((wet_onset_s - reference_onset_s).abs() < 1e-4, "{} s vs {} s", wet_onset_s, reference_onset_s);
+
+        let reference_rt60_s = crate::reverb::measure_rt60_s(&reference, SAMPLE_RATE as f64);
+        let rt60_s = crate::reverb::measure_rt60_s(&impulse_response, 96000.0);
+        assert!((rt60_s - reference_rt60_s).abs() / reference_rt60_s < 0.05, "{} s vs {} s", rt60_s, reference_rt60_s);
+    }
+
+    #[test]
+    fn test_pre_delay_gap_is_not_reported_as_silence() {
+        let mut plugin = SchroederPlugin::default();
+        let params = plugin.params.clone();
+        params.pre_delay.smoothed.reset(250.0);
+        plugin.processor.prepare(SAMPLE_RATE as f64, (params.rt60.value() * 1000.0) as f64);
+        plugin.processor.set_pre_delay(250.0);
+        plugin.reset();
+
+        let mut wet_arrived = false;
+        for block_start in (0..SAMPLE_RATE as usize).step_by(MAX_BLOCK_SIZE) {
+            let mut dry = [[0.0; MAX_BLOCK_SIZE]; 2];
+            if block_start == 0 {
+                dry[0][0] = 1.0;
+                dry[1][0] = 1.0;
+            }
+            let [mut left, mut right] = dry;
+            let key = left.map(f32::abs);
+            process_block(&params, &TempoSync::default(), &mut plugin.processor, &mut left, &mut right, &key);
+            plugin.track_silence([&dry[0], &dry[1]], [&left, &right]);
+
+            wet_arrived |= block_start > 0 && plugin.silent_samples < MAX_BLOCK_SIZE as u32;
+            if wet_arrived {
+                break;
+            }
+            assert!(!matches!(plugin.process_status(), ProcessStatus::Normal), "Normal after {} samples", block_start);
+        }
+        assert!(wet_arrived);
+    }
+
+    #[test]
+    fn test_synced_lfo_phase_is_continuous_across_bar_lines() {
+        // A dotted quarter does not divide a 4/4 bar, so realigning at every
+        // bar line would make the phase jump at beat 4.
+        let division_beats = NoteDivision::QuarterDotted.beats();
+        let beats_per_block = 0.1;
+        let expected_step = beats_per_block / division_beats;
+        let mut previous = lfo_phase_at(3.0, division_beats);
+        for block in 1..=20 {
+            let phase = lfo_phase_at(3.0 + block as f64 * beats_per_block, division_beats);
+            let step = (phase - previous).rem_euclid(1.0);
+            assert!((step - expected_step).abs() < 1e-9, "step {} at block {}", step, block);
+            previous = phase;
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use nih_plug::prelude::Enum;
+use crate::float::Float;
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Schroeder,
+    Moorer,
+    Freeverb,
+    #[name = "FDN"]
+    Fdn,
+}
+
+/// Mono late-reverb network that `StereoReverb` can switch between. Every
+/// implementor returns the wet signal only; pre-delay, width and the dry/wet
+/// mix are shared and handled by `StereoReverb`. Settings are always `f64`;
+/// the signal is in the sample type `T`.
+pub trait Reverb<T: Float = f64> {
+    fn prepare(&mut self, sample_rate: f64, rt60_ms: f64);
+    fn update_reverb_time(&mut self, rt60_ms: f64);
+    fn set_dampening(&mut self, dampening: f64);
+    /// Decay time of the low and high bands relative to the RT60. The high
+    /// band can only decay as fast as or faster than the mids.
+    fn set_band_decay(&mut self, low_multiplier: f64, high_multiplier: f64);
+    /// Where the low and high bands of `set_band_decay` start.
+    fn set_crossovers(&mut self, low_hz: f64, high_hz: f64);
+    /// Shifts the network's delays by `offset_ms` to decorrelate channels,
+    /// crossfading to the new lengths so it can be automated.
+    fn set_delay_offset(&mut self, offset_ms: f64);
+    /// Longest the network can take to pass an input on, and longest gap
+    /// between echoes while its tail rings.
+    fn max_delay_ms(&self) -> f64;
+    /// Silences the tail, keeping every setting.
+    fn reset(&mut self);
+    fn process_wet(&mut self, input: T) -> T;
+
+    /// Block version of `process_wet`, `input` and `output` are the same
+    /// length.
+    fn process_wet_block(&mut self, input: &[T], output: &mut [T]) {
+        for (input, output) in input.iter().zip(output.iter_mut()) {
+            *output = self.process_wet(*input);
+        }
+    }
+}
+
+pub(crate) const DEFAULT_LOW_CROSSOVER_HZ: f64 = 250.0;
+pub(crate) const DEFAULT_HIGH_CROSSOVER_HZ: f64 = 4000.0;
+
+pub(crate) fn get_length_in_samples(length_ms: f64, sample_rate_hz: f64) -> f64 {
+    sample_rate_hz * length_ms / 1000.0
+}
+
+/// Feedback gain that makes a loop of `delay_ms` decay by 60 dB in `rt60_ms`.
+pub(crate) fn get_gain_from_rt60(delay_ms: f64, rt60_ms: f64) -> f64 {
+    let base: f64 = 10.0;
+    base.powf(-3.0 * delay_ms / rt60_ms)
+}
+
+/// Loop gains in the low, mid and high bands that make a loop of `delay_ms`
+/// decay by 60 dB in `rt60_ms` times the (low, high) `multipliers`, and in
+/// `rt60_ms` in the mids.
+pub(crate) fn get_band_gains_from_rt60(delay_ms: f64, rt60_ms: f64, multipliers: (f64, f64)) -> (f64, f64, f64) {
+    (
+        get_gain_from_rt60(delay_ms, rt60_ms * multipliers.0),
+        get_gain_from_rt60(delay_ms, rt60_ms),
+        get_gain_from_rt60(delay_ms, rt60_ms * multipliers.1),
+    )
+}
+
+/// Estimates the RT60 of an impulse response from the slope of its Schroeder
+/// energy decay curve between -5 dB and -35 dB.
+#[cfg(test)]
+pub(crate) fn measure_rt60_s(impulse_response: &[f64], sample_rate: f64) -> f64 {
+    let mut remaining: f64 = impulse_response.iter().map(|x| x * x).sum();
+    let total = remaining;
+    let (mut start, mut end) = (None, None);
+    for (n, x) in impulse_response.iter().enumerate() {
+        let level_db = 10.0 * (remaining / total).log10();
+        if start.is_none() && level_db <= -5.0 {
+            start = Some(n);
+        }
+        if end.is_none() && level_db <= -35.0 {
+            end = Some(n);
+        }
+        remaining -= x * x;
+    }
+    let decay_samples = (end.unwrap() - start.unwrap()) as f64;
+    2.0 * decay_samples / sample_rate
+}
+
+/// RT60 within `band_hz`, from the slope of the band's energy over
+/// successive windowed frames of the impulse response.
+#[cfg(test)]
+pub(crate) fn measure_band_rt60_s(impulse_response: &[f64], sample_rate: f64, band_hz: (f64, f64)) -> f64 {
+    const FRAME: usize = 4096;
+    const HOP: usize = 2048;
+    let bin_hz = sample_rate / FRAME as f64;
+    let bins = (band_hz.0 / bin_hz).ceil() as usize..=(band_hz.1 / bin_hz).floor() as usize;
+    let window: Vec<f64> = (0..FRAME)
+        .map(|n| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * n as f64 / FRAME as f64).cos())
+        .collect();
+
+    let levels_db: Vec<f64> = impulse_response
+        .windows(FRAME)
+        .step_by(HOP)
+        .map(|frame| {
+            let energy: f64 = bins
+                .clone()
+                .map(|bin| {
+                    // Goertzel recurrence for a single DFT bin.
+                    let coefficient = 2.0 * (2.0 * std::f64::consts::PI * bin as f64 / FRAME as f64).cos();
+                    let (s1, s2) = frame.iter().zip(window.iter()).fold((0.0, 0.0), |(s1, s2), (x, w)| {
+                        (x * w + coefficient * s1 - s2, s1)
+                    });
+                    s1 * s1 + s2 * s2 - coefficient * s1 * s2
+                })
+                .sum();
+            10.0 * energy.log10()
+        })
+        .collect();
+
+    // Least-squares slope over the frames after the build-up, down to 40 dB
+    // below the first of them.
+    let first = 2;
+    let last = (first..levels_db.len()).take_while(|&i| levels_db[i] > levels_db[first] - 40.0).last().unwrap();
+    let points: Vec<(f64, f64)> = (first..=last).map(|i| ((i * HOP) as f64 / sample_rate, levels_db[i])).collect();
+    let count = points.len() as f64;
+    let mean_t = points.iter().map(|p| p.0).sum::<f64>() / count;
+    let mean_db = points.iter().map(|p| p.1).sum::<f64>() / count;
+    let covariance: f64 = points.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_db)).sum();
+    let variance: f64 = points.iter().map(|p| (p.0 - mean_t).powi(2)).sum();
+    -60.0 / (covariance / variance)
+}
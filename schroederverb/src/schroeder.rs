@@ -1,8 +1,14 @@
-use euterpe_rs::all_pass::AllPass;
-use euterpe_rs::comb::Comb;
 use euterpe_rs::processor::AudioProcessor;
-use euterpe_rs::mod_all_pass::ModAllPass;
-use euterpe_rs::lfo::WaveformType;
+use crate::all_pass::AllPass;
+use crate::block::{BlockProcessor, MAX_BLOCK_SIZE};
+use crate::delay_line::Interpolation;
+use crate::float::Float;
+use crate::lfo::Waveform;
+use crate::mod_all_pass::ModAllPass;
+use crate::comb_bank::CombBank;
+use crate::reverb::{
+    get_band_gains_from_rt60, get_length_in_samples, Reverb, DEFAULT_HIGH_CROSSOVER_HZ, DEFAULT_LOW_CROSSOVER_HZ,
+};
 
 const NUM_COMBS: usize = 8;
 const NUM_APF: usize = 2;
@@ -10,46 +16,69 @@ const NUM_PRE_APF: usize = 2;
 const COMB_DELAYS_MS: [f64; NUM_COMBS] = [29.7, 31.4, 32.2, 35.6, 38.1, 40.9, 45.6, 48.5];
 const APF_DELAYS_MS: [f64; NUM_APF] = [2.3, 3.7];
 const PRE_APF_DELAYS_MS : [f64; NUM_PRE_APF] = [1.0, 2.0];
-const COMB_MAX_DELAY_MS: f64 = 50.0;
+/// Longest comb at the largest size, plus the stereo offset and the deepest
+/// modulation.
+const COMB_MAX_DELAY_MS: f64 = 105.0;
+const SIZE_MIN: f64 = 0.25;
+const SIZE_MAX: f64 = 2.0;
+/// Each comb's modulation rate is the Mod Rate scaled by a factor spread
+/// evenly over this range around 1.0, so the sweeps never line up.
+const COMB_MOD_RATE_SPREAD: f64 = 0.3;
+/// The modulated reads land between samples, where linear interpolation
+/// would dull the tail. The fixed lengths are whole samples and read exactly
+/// either way.
+const MOD_INTERPOLATION: Interpolation = Interpolation::CubicHermite;
 const APF_MAX_DELAY_MS: f64 = 20.0;
-const MOD_DELAY_DELAY_MS : f32 = 30.0;
-const MOD_DELAY_LFO_FREQ_HZ : f32 = 10.0;
+const MOD_DELAY_DELAY_MS : f64 = 30.0;
+const MOD_DELAY_DEPTH_MS : f64 = 1.0;
+const MOD_DELAY_MAX_DEPTH_MS : f64 = 10.0;
+const MOD_DELAY_LFO_FREQ_HZ : f64 = 10.0;
+const MOD_APF_GAIN: f64 = 0.5;
+const APF_GAIN: f64 = 0.5 * std::f64::consts::SQRT_2;
+/// Time the feedback and input take to move into and out of freeze.
+pub(crate) const FREEZE_RAMP_MS: f64 = 50.0;
 
-
-fn get_length_in_samples(length_ms: f64, sample_rate_hz: f64) -> f64 {
-    sample_rate_hz * length_ms / 1000.0
-}
-
-fn get_gain_from_rt60(delay_ms: f64, rt60_ms: f64) -> f64 {
-    let base: f64 = 10.0;
-    base.powf(-3.0 * delay_ms / rt60_ms)
-}
-
-pub struct Schroeder {
-    combs: [(Comb, f64); NUM_COMBS],
-    all_passes: [(AllPass, f64); NUM_APF],
-    pre_all_passes: [(AllPass, f64); NUM_PRE_APF],
-    mod_all_pass : ModAllPass,
+pub struct Schroeder<T: Float = f64> {
+    /// One lane per entry of `COMB_DELAYS_MS`.
+    combs: CombBank<T, NUM_COMBS>,
+    all_passes: [(AllPass<T>, f64); NUM_APF],
+    pre_all_passes: [(AllPass<T>, f64); NUM_PRE_APF],
+    mod_all_pass : ModAllPass<T>,
     mod_enabled : bool,
     dry_wet_mix: f64,
+    sample_rate: f64,
+    rt60_ms: f64,
+    delay_offset_ms: f64,
+    dampening: f64,
+    low_multiplier: f64,
+    high_multiplier: f64,
+    crossovers_hz: (f64, f64),
+    mod_lfo_freq: f32,
+    mod_depth_ms: f64,
+    mod_waveform: Waveform,
+    diffusion: f64,
+    comb_mod_depth_ms: f64,
+    comb_mod_rate_hz: f64,
+    size: f64,
+    frozen: bool,
+    /// 0.0 runs the normal network, 1.0 is fully frozen.
+    freeze_amount: f64,
 }
 
-impl Schroeder {
+impl<T: Float> Schroeder<T> {
     pub fn new(sample_rate: f64) -> Self {
         let comb_delay_length = get_length_in_samples(COMB_MAX_DELAY_MS, sample_rate) as usize;
         let apf_delay_length = get_length_in_samples(APF_MAX_DELAY_MS, sample_rate) as usize;
 
         Schroeder {
-            combs: [
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[0]),
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[1]),
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[2]),
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[3]),
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[4]),
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[5]),
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[6]),
-                (Comb::new(comb_delay_length, true), COMB_DELAYS_MS[7]),
-            ],
+            combs: {
+                let mut combs = CombBank::new(comb_delay_length, MOD_INTERPOLATION, sample_rate);
+                // Spread the LFO phases around the cycle.
+                for index in 0..NUM_COMBS {
+                    combs.set_lfo_phase(index, index as f64 / NUM_COMBS as f64);
+                }
+                combs
+            },
             all_passes: [
                 (AllPass::new(apf_delay_length), APF_DELAYS_MS[0]),
                 (AllPass::new(apf_delay_length), APF_DELAYS_MS[1]),
@@ -58,33 +87,197 @@ impl Schroeder {
                 (AllPass::new(apf_delay_length), PRE_APF_DELAYS_MS[0]),
                 (AllPass::new(apf_delay_length), PRE_APF_DELAYS_MS[1]),
             ],
-            mod_all_pass: ModAllPass::new(MOD_DELAY_DELAY_MS,MOD_DELAY_LFO_FREQ_HZ,WaveformType::Triangle,sample_rate as f32),
+            mod_all_pass: ModAllPass::new(
+                MOD_DELAY_DELAY_MS,
+                MOD_DELAY_DEPTH_MS,
+                MOD_DELAY_MAX_DEPTH_MS,
+                MOD_DELAY_LFO_FREQ_HZ,
+                MOD_APF_GAIN,
+                MOD_INTERPOLATION,
+                sample_rate,
+            ),
             mod_enabled: false,
             dry_wet_mix: 0.5,
+            sample_rate,
+            rt60_ms: 0.0,
+            delay_offset_ms: 0.0,
+            dampening: 0.0,
+            low_multiplier: 1.0,
+            high_multiplier: 1.0,
+            crossovers_hz: (DEFAULT_LOW_CROSSOVER_HZ, DEFAULT_HIGH_CROSSOVER_HZ),
+            mod_lfo_freq: MOD_DELAY_LFO_FREQ_HZ as f32,
+            mod_depth_ms: MOD_DELAY_DEPTH_MS,
+            mod_waveform: Waveform::Triangle,
+            diffusion: 1.0,
+            comb_mod_depth_ms: 0.0,
+            comb_mod_rate_hz: 0.0,
+            size: 1.0,
+            frozen: false,
+            freeze_amount: 0.0,
         }
     }
 
     pub fn prepare(&mut self, sample_rate: f64, rt60_ms: f64) {
-        for (comb, delay_ms) in self.combs.iter_mut() {
-            let delay_samples = get_length_in_samples(*delay_ms, sample_rate);
-            let gain = get_gain_from_rt60(*delay_ms, rt60_ms);
-            comb.prepare(delay_samples, gain);
+        self.sample_rate = sample_rate;
+        self.rt60_ms = rt60_ms;
+
+        for (index, delay_ms) in COMB_DELAYS_MS.into_iter().enumerate() {
+            let delay_samples = get_length_in_samples(self.scaled_delay_ms(delay_ms), sample_rate);
+            self.combs.prepare(index, delay_samples);
+        }
+        self.update_feedback();
+
+        let gain = self.diffusion * APF_GAIN;
+        for (all_pass, delay_ms) in self.pre_all_passes.iter_mut() {
+            all_pass.prepare(get_length_in_samples(*delay_ms * self.size, sample_rate), gain);
+        }
+
+        for index in 0..NUM_APF {
+            let delay_samples = get_length_in_samples(self.scaled_delay_ms(self.all_passes[index].1), sample_rate);
+            self.all_passes[index].0.prepare(delay_samples, APF_GAIN);
+        }
+    }
+
+    /// Length of a comb or all-pass at the current size and stereo offset.
+    fn scaled_delay_ms(&self, delay_ms: f64) -> f64 {
+        delay_ms * self.size + self.delay_offset_ms
+    }
+
+    /// Scales every comb, all-pass and pre-diffusion delay, from 0.25 for a
+    /// small, dense space to 2.0 for a large, sparse one. The delays crossfade
+    /// to their new lengths and the comb gains follow, so the RT60 holds.
+    pub fn set_size(&mut self, size: f64) {
+        let size = size.clamp(SIZE_MIN, SIZE_MAX);
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+
+        for (all_pass, delay_ms) in self.pre_all_passes.iter_mut() {
+            all_pass.set_delay(get_length_in_samples(*delay_ms * size, self.sample_rate));
+        }
+        self.update_delays();
+    }
+
+    /// Crossfades the combs and all-passes to their lengths at the current
+    /// size and stereo offset. The comb gains follow, so the RT60 holds.
+    fn update_delays(&mut self) {
+        for (index, delay_ms) in COMB_DELAYS_MS.into_iter().enumerate() {
+            let delay_samples = get_length_in_samples(self.scaled_delay_ms(delay_ms), self.sample_rate);
+            self.combs.set_delay(index, delay_samples);
+        }
+        for index in 0..NUM_APF {
+            let delay_samples = get_length_in_samples(self.scaled_delay_ms(self.all_passes[index].1), self.sample_rate);
+            self.all_passes[index].0.set_delay(delay_samples);
+        }
+        self.update_feedback();
+    }
+
+    /// Scales the gain of the pre-diffusion all-passes. 0.0 turns the cascade
+    /// into a plain delay, 1.0 gives the classic gain of 0.5 * sqrt(2).
+    pub fn set_diffusion(&mut self, diffusion: f64) {
+        let diffusion = diffusion.clamp(0.0, 1.0);
+        if diffusion == self.diffusion {
+            return;
         }
+        self.diffusion = diffusion;
+        for (all_pass, _) in self.pre_all_passes.iter_mut() {
+            all_pass.set_gain(diffusion * APF_GAIN);
+        }
+    }
 
-        self.pre_all_passes.iter_mut().for_each(|(apf, delay)| {
-            (*apf).prepare(get_length_in_samples(*delay, sample_rate),0.5 * (2f64).sqrt());
-        });
+    /// Shifts every comb and all-pass delay by `offset_ms`. Two instances with
+    /// different offsets produce decorrelated tails for the same input. The
+    /// delays crossfade to their new lengths, as they do for `set_size`.
+    pub fn set_delay_offset(&mut self, offset_ms: f64) {
+        if offset_ms == self.delay_offset_ms {
+            return;
+        }
+        self.delay_offset_ms = offset_ms;
+        self.update_delays();
+    }
 
-        for (all_pass, delay_ms) in self.all_passes.iter_mut() {
-            let delay_samples = get_length_in_samples(*delay_ms, sample_rate);
-            all_pass.prepare(delay_samples, 0.5 * (2f64).sqrt());
+    /// Silences the tail and restarts the modulation LFOs, keeping every
+    /// setting.
+    pub fn reset(&mut self) {
+        self.combs.reset();
+        for index in 0..NUM_COMBS {
+            self.combs.set_lfo_phase(index, index as f64 / NUM_COMBS as f64);
+        }
+        for (all_pass, _) in self.pre_all_passes.iter_mut().chain(self.all_passes.iter_mut()) {
+            all_pass.reset();
         }
+        self.mod_all_pass.reset();
     }
 
     pub fn set_dampening(&mut self, dampening: f64) {
-        for (comb, _) in self.combs.iter_mut() {
-            comb.set_dampening(dampening);
+        self.dampening = dampening;
+        self.update_feedback();
+    }
+
+    /// Decay time of the low and high bands relative to the RT60. The high
+    /// band can only decay as fast as or faster than the mids.
+    pub fn set_band_decay(&mut self, low_multiplier: f64, high_multiplier: f64) {
+        let high_multiplier = high_multiplier.min(1.0);
+        if (low_multiplier, high_multiplier) == (self.low_multiplier, self.high_multiplier) {
+            return;
+        }
+        self.low_multiplier = low_multiplier;
+        self.high_multiplier = high_multiplier;
+        self.update_feedback();
+    }
+
+    pub fn set_crossovers(&mut self, low_hz: f64, high_hz: f64) {
+        if (low_hz, high_hz) == self.crossovers_hz {
+            return;
         }
+        self.crossovers_hz = (low_hz, high_hz);
+        self.update_feedback();
+    }
+
+    /// Holds the current tail: the combs' feedback goes to unity without
+    /// dampening or delay modulation and the input is muted, all over
+    /// `FREEZE_RAMP_MS`.
+    /// Unfreezing ramps back to the RT60 and dampening as set.
+    pub fn set_freeze(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Low, mid and high loop gains of a comb of `delay_ms`, each moved
+    /// towards unity by the freeze amount.
+    fn comb_gains(&self, delay_ms: f64) -> (f64, f64, f64) {
+        let multipliers = (self.low_multiplier, self.high_multiplier);
+        let (low, mid, high) = get_band_gains_from_rt60(self.scaled_delay_ms(delay_ms), self.rt60_ms, multipliers);
+        let freeze = |gain: f64| gain + (1.0 - gain) * self.freeze_amount;
+        (freeze(low), freeze(mid), freeze(high))
+    }
+
+    fn update_feedback(&mut self) {
+        let dampening = self.dampening * (1.0 - self.freeze_amount);
+        for (index, delay_ms) in COMB_DELAYS_MS.into_iter().enumerate() {
+            let gains = self.comb_gains(delay_ms);
+            self.combs.set_band_gains(index, gains, self.crossovers_hz);
+            self.combs.set_dampening(index, dampening);
+        }
+    }
+
+    fn freeze_is_ramping(&self) -> bool {
+        self.freeze_amount != if self.frozen { 1.0 } else { 0.0 }
+    }
+
+    /// Moves the freeze amount one sample towards the freeze switch.
+    fn advance_freeze(&mut self) {
+        if !self.freeze_is_ramping() {
+            return;
+        }
+        let step = 1.0 / get_length_in_samples(FREEZE_RAMP_MS, self.sample_rate);
+        self.freeze_amount = if self.frozen {
+            (self.freeze_amount + step).min(1.0)
+        } else {
+            (self.freeze_amount - step).max(0.0)
+        };
+        self.update_feedback();
+        self.update_comb_modulation();
     }
 
     pub fn set_dry_wet_mix(&mut self, dry_wet_mix: f64) {
@@ -96,33 +289,69 @@ impl Schroeder {
     }
 
     pub fn set_mod_lfo_freq(&mut self, freq: f32) {
-        self.mod_all_pass.set_lfo_freq(freq);
+        self.mod_lfo_freq = freq;
+        self.mod_all_pass.set_lfo_freq(freq as f64);
     }
 
-    pub fn update_reverb_time(&mut self, rt60_ms: f64) {
-        for (comb, delay_ms) in self.combs.iter_mut() {
-            comb.set_gain(get_gain_from_rt60(*delay_ms, rt60_ms))
+    /// Sweeps every comb's delay by up to `depth_ms` either way, each with
+    /// its own LFO phase and a rate spread around `rate_hz`. Breaks up the
+    /// metallic ringing of the fixed comb lengths.
+    pub fn set_comb_modulation(&mut self, depth_ms: f64, rate_hz: f64) {
+        if (depth_ms, rate_hz) == (self.comb_mod_depth_ms, self.comb_mod_rate_hz) {
+            return;
         }
+        self.comb_mod_depth_ms = depth_ms;
+        self.comb_mod_rate_hz = rate_hz;
+        self.update_comb_modulation();
     }
-}
 
-impl AudioProcessor<f64> for Schroeder {
-    fn process(&mut self, input: f64) -> f64 {
-        let mut out: f64 = 0.0;
-        let mut pre_apf_out: f64 = 0.0;
-        
-        for (all_pass, _) in self.pre_all_passes.iter_mut() {
-            pre_apf_out = all_pass.process(input);
+    /// Modulated reads interpolate and lose a little energy on every pass, so
+    /// the depth fades out with the freeze amount to keep the frozen loop
+    /// lossless.
+    fn update_comb_modulation(&mut self) {
+        let depth_samples = get_length_in_samples(self.comb_mod_depth_ms, self.sample_rate) * (1.0 - self.freeze_amount);
+        for index in 0..NUM_COMBS {
+            let spread = COMB_MOD_RATE_SPREAD * (index as f64 / (NUM_COMBS - 1) as f64 - 0.5);
+            self.combs.set_modulation(index, depth_samples, self.comb_mod_rate_hz * (1.0 + spread));
         }
+    }
 
-        for (index, (combs, _)) in self.combs.iter_mut().enumerate() {
-            let mut comb_out = combs.process(pre_apf_out);
-            if index % 2 == 0 {
-                comb_out *= -1.0;
-            };
-            out += comb_out;
+    /// How far the modulated all-pass sweeps either way of its 30 ms delay,
+    /// up to 10 ms.
+    pub fn set_mod_depth(&mut self, depth_ms: f64) {
+        if depth_ms == self.mod_depth_ms {
+            return;
         }
-        out /= NUM_COMBS as f64;
+        self.mod_depth_ms = depth_ms;
+        self.mod_all_pass.set_depth(depth_ms);
+    }
+
+    pub fn set_mod_waveform(&mut self, waveform: Waveform) {
+        self.mod_waveform = waveform;
+        self.mod_all_pass.set_waveform(waveform);
+    }
+
+    /// Moves the modulation LFO to `phase`, from 0.0 to 1.0 of a cycle.
+    pub fn set_mod_lfo_phase(&mut self, phase: f64) {
+        self.mod_all_pass.set_lfo_phase(phase);
+    }
+
+    pub fn update_reverb_time(&mut self, rt60_ms: f64) {
+        if rt60_ms == self.rt60_ms {
+            return;
+        }
+        self.rt60_ms = rt60_ms;
+        self.update_feedback();
+    }
+}
+
+impl<T: Float> Schroeder<T> {
+    /// Runs `input` through the network and returns the wet signal only,
+    /// leaving the dry/wet mix to the caller.
+    pub fn process_wet(&mut self, input: T) -> T {
+        self.advance_freeze();
+        let pre_apf_out = self.diffuse(input * T::from_f64(1.0 - self.freeze_amount));
+        let mut out = self.sum_combs(pre_apf_out);
 
         if self.mod_enabled {
             out = self.mod_all_pass.process(out);
@@ -132,22 +361,409 @@ impl AudioProcessor<f64> for Schroeder {
             out = all_pass.process(out);
         }
 
+        out
+    }
+
+    /// Block version of `process_wet`. Each stage runs over up to
+    /// `MAX_BLOCK_SIZE` samples before the next one, except while the freeze
+    /// ramp moves the comb gains from sample to sample.
+    pub fn process_wet_block(&mut self, input: &[T], output: &mut [T]) {
+        for (input, output) in input.chunks(MAX_BLOCK_SIZE).zip(output.chunks_mut(MAX_BLOCK_SIZE)) {
+            if self.freeze_is_ramping() {
+                for (input, output) in input.iter().zip(output.iter_mut()) {
+                    *output = self.process_wet(*input);
+                }
+                continue;
+            }
+
+            let mut diffused = [T::ZERO; MAX_BLOCK_SIZE];
+            let diffused = &mut diffused[..input.len()];
+            let input_gain = T::from_f64(1.0 - self.freeze_amount);
+            for (diffused, input) in diffused.iter_mut().zip(input.iter()) {
+                *diffused = *input * input_gain;
+            }
+            for (all_pass, _) in self.pre_all_passes.iter_mut() {
+                all_pass.process_block_in_place(diffused);
+            }
+
+            for (out, diffused) in output.iter_mut().zip(diffused.iter()) {
+                *out = self.sum_combs(*diffused);
+            }
+
+            if self.mod_enabled {
+                self.mod_all_pass.process_block_in_place(output);
+            }
+            for (all_pass, _) in self.all_passes.iter_mut() {
+                all_pass.process_block_in_place(output);
+            }
+        }
+    }
+
+    /// Runs the comb bank and averages the combs, every other one inverted.
+    fn sum_combs(&mut self, input: T) -> T {
+        let mut out = T::ZERO;
+        for (index, mut comb_out) in self.combs.process(input).into_iter().enumerate() {
+            if index % 2 == 0 {
+                comb_out = -comb_out;
+            };
+            out += comb_out;
+        }
+        out / T::from_f64(NUM_COMBS as f64)
+    }
+
+    /// Runs the pre-diffusion all-passes in series.
+    fn diffuse(&mut self, input: T) -> T {
+        self.pre_all_passes
+            .iter_mut()
+            .fold(input, |out, (all_pass, _)| all_pass.process(out))
+    }
+}
+
+impl<T: Float> Reverb<T> for Schroeder<T> {
+    fn prepare(&mut self, sample_rate: f64, rt60_ms: f64) {
+        Schroeder::prepare(self, sample_rate, rt60_ms);
+    }
+
+    fn update_reverb_time(&mut self, rt60_ms: f64) {
+        Schroeder::update_reverb_time(self, rt60_ms);
+    }
+
+    fn set_dampening(&mut self, dampening: f64) {
+        Schroeder::set_dampening(self, dampening);
+    }
+
+    fn set_band_decay(&mut self, low_multiplier: f64, high_multiplier: f64) {
+        Schroeder::set_band_decay(self, low_multiplier, high_multiplier);
+    }
+
+    fn set_crossovers(&mut self, low_hz: f64, high_hz: f64) {
+        Schroeder::set_crossovers(self, low_hz, high_hz);
+    }
+
+    fn set_delay_offset(&mut self, offset_ms: f64) {
+        Schroeder::set_delay_offset(self, offset_ms);
+    }
+
+    fn max_delay_ms(&self) -> f64 {
+        COMB_MAX_DELAY_MS
+    }
+
+    fn reset(&mut self) {
+        Schroeder::reset(self);
+    }
+
+    fn process_wet(&mut self, input: T) -> T {
+        Schroeder::process_wet(self, input)
+    }
+
+    fn process_wet_block(&mut self, input: &[T], output: &mut [T]) {
+        Schroeder::process_wet_block(self, input, output);
+    }
+}
 
-        out * self.dry_wet_mix + input * (1.0 - self.dry_wet_mix)
+impl<T: Float> AudioProcessor<T> for Schroeder<T> {
+    fn process(&mut self, input: T) -> T {
+        let out = self.process_wet(input);
+        out * T::from_f64(self.dry_wet_mix) + input * T::from_f64(1.0 - self.dry_wet_mix)
+    }
+}
+
+impl<T: Float> BlockProcessor<T> for Schroeder<T> {
+    fn process_block(&mut self, input: &[T], output: &mut [T]) {
+        self.process_wet_block(input, output);
+        let (wet_gain, dry_gain) = (T::from_f64(self.dry_wet_mix), T::from_f64(1.0 - self.dry_wet_mix));
+        for (output, input) in output.iter_mut().zip(input.iter()) {
+            *output = *output * wet_gain + *input * dry_gain;
+        }
+    }
+
+    fn process_block_in_place(&mut self, buffer: &mut [T]) {
+        for chunk in buffer.chunks_mut(MAX_BLOCK_SIZE) {
+            let mut dry = [T::ZERO; MAX_BLOCK_SIZE];
+            let dry = &mut dry[..chunk.len()];
+            dry.copy_from_slice(chunk);
+            self.process_block(dry, chunk);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    #[test]
-    fn test_creation() {
-        use super::*;
+    use super::*;
+    use crate::reverb::measure_band_rt60_s;
+    use crate::test_util::{count_allocations, max_step, noise};
+
+    fn creation<T: Float>() {
         let sample_rate = 44100.0;
         let rt60_ms = 20.0;
-        let mut uut = Schroeder::new(sample_rate);
+        let mut uut = Schroeder::<T>::new(sample_rate);
         uut.prepare(sample_rate, rt60_ms);
         uut.set_dampening(0.5);
         uut.set_dry_wet_mix(0.6);
-        let _out = uut.process(0.3);
+        let _out = uut.process(T::from_f64(0.3));
+    }
+
+    #[test]
+    fn test_creation() {
+        creation::<f32>();
+        creation::<f64>();
+    }
+
+    fn measure_rt60_s<T: Float>(sample_rate: f64, rt60_ms: f64) -> f64 {
+        let mut uut = Schroeder::<T>::new(sample_rate);
+        uut.prepare(sample_rate, rt60_ms);
+        uut.set_dampening(0.0);
+
+        let num_samples = (sample_rate * rt60_ms / 1000.0) as usize;
+        let impulse_response: Vec<f64> = (0..num_samples)
+            .map(|n| uut.process_wet(if n == 0 { T::ONE } else { T::ZERO }).to_f64())
+            .collect();
+        crate::reverb::measure_rt60_s(&impulse_response, sample_rate)
+    }
+
+    fn pre_diffusion_in_series<T: Float>(max_energy_error: f64) {
+        let sample_rate = 44100.0;
+        let mut uut = Schroeder::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+
+        let apf_delay_length = get_length_in_samples(APF_MAX_DELAY_MS, sample_rate) as usize;
+        let mut reference: Vec<AllPass<T>> = PRE_APF_DELAYS_MS
+            .iter()
+            .map(|delay_ms| {
+                let mut all_pass = AllPass::new(apf_delay_length);
+                all_pass.prepare(get_length_in_samples(*delay_ms, sample_rate), APF_GAIN);
+                all_pass
+            })
+            .collect();
+
+        let mut energy = 0.0;
+        for n in 0..4410 {
+            let input = if n == 0 { T::ONE } else { T::ZERO };
+            let expected = reference.iter_mut().fold(input, |out, all_pass| all_pass.process(out));
+            let out = uut.diffuse(input);
+            assert_eq!(out, expected);
+            energy += out.to_f64() * out.to_f64();
+        }
+        // A cascade of all-passes is lossless.
+        assert!((energy - 1.0).abs() < max_energy_error);
+    }
+
+    #[test]
+    fn test_pre_diffusion_in_series() {
+        pre_diffusion_in_series::<f32>(1e-5);
+        pre_diffusion_in_series::<f64>(1e-6);
+    }
+
+    fn reset_clears_tail<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut uut = Schroeder::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 5000.0);
+        uut.set_dampening(0.5);
+        uut.set_mod_enabled(true);
+
+        let mut seed: u32 = 1;
+        for _ in 0..44100 {
+            uut.process(noise(&mut seed));
+        }
+
+        uut.reset();
+        for _ in 0..44100 {
+            assert_eq!(uut.process(T::ZERO), T::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_reset_clears_tail() {
+        reset_clears_tail::<f32>();
+        reset_clears_tail::<f64>();
+    }
+
+    fn reset_keeps_settings<T: Float>() {
+        let sample_rate = 44100.0;
+        let configure = |uut: &mut Schroeder<T>| {
+            uut.set_size(1.5);
+            uut.set_diffusion(0.6);
+            uut.set_delay_offset(0.7);
+            uut.set_comb_modulation(1.0, 0.5);
+            uut.set_mod_enabled(true);
+            uut.set_mod_depth(3.0);
+            uut.set_mod_lfo_freq(2.0);
+            uut.set_mod_waveform(Waveform::Sine);
+            uut.set_dry_wet_mix(0.7);
+            // Last, so the new lengths apply at once rather than crossfading.
+            uut.prepare(sample_rate, 3000.0);
+            uut.set_dampening(0.3);
+        };
+        let mut uut = Schroeder::<T>::new(sample_rate);
+        let mut reference = Schroeder::<T>::new(sample_rate);
+        configure(&mut uut);
+        configure(&mut reference);
+
+        let mut seed: u32 = 1;
+        for _ in 0..44100 {
+            uut.process(noise(&mut seed));
+        }
+
+        // Runs on the audio thread, so it must not touch the heap.
+        assert_eq!(count_allocations(|| uut.reset()), 0);
+        for n in 0..44100 {
+            let input = noise(&mut seed);
+            assert_eq!(uut.process(input), reference.process(input), "sample {}", n);
+        }
+    }
+
+    #[test]
+    fn test_reset_keeps_settings() {
+        reset_keeps_settings::<f32>();
+        reset_keeps_settings::<f64>();
+    }
+
+    fn freeze_holds_energy<T: Float>(comb_mod_depth_ms: f64) {
+        let sample_rate = 44100.0;
+        let mut uut = Schroeder::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_dampening(0.5);
+        uut.set_mod_enabled(true);
+        uut.set_comb_modulation(comb_mod_depth_ms, 0.5);
+
+        let mut seed: u32 = 1;
+        for _ in 0..44100 {
+            uut.process_wet(noise(&mut seed));
+        }
+
+        // The input keeps coming but must not reach the frozen tail.
+        uut.set_freeze(true);
+        let one_second = sample_rate as usize;
+        let energies: Vec<f64> = (0..60)
+            .map(|_| {
+                (0..one_second)
+                    .map(|_| uut.process_wet(noise(&mut seed)).to_f64().powi(2))
+                    .sum::<f64>()
+            })
+            .collect();
+        for energy in energies.iter().skip(1) {
+            let change_db = 10.0 * (energy / energies[1]).log10();
+            assert!(change_db.abs() < 1.0, "{} ms: {} dB", comb_mod_depth_ms, change_db);
+        }
+
+        // Back to a 2 s decay once released.
+        uut.set_freeze(false);
+        let released: f64 = (0..2 * one_second).map(|_| uut.process_wet(T::ZERO).to_f64().powi(2)).sum();
+        let tail: f64 = (0..one_second).map(|_| uut.process_wet(T::ZERO).to_f64().powi(2)).sum();
+        assert!(released < 2.0 * energies[59]);
+        assert!(tail < energies[59] * 1e-4);
+    }
+
+    #[test]
+    fn test_freeze_holds_energy() {
+        for comb_mod_depth_ms in [0.0, 1.0] {
+            freeze_holds_energy::<f32>(comb_mod_depth_ms);
+            freeze_holds_energy::<f64>(comb_mod_depth_ms);
+        }
+    }
+
+    /// Largest sample-to-sample step of the wet output over `num_samples` of
+    /// a steady sine.
+    fn wet_max_step<T: Float>(uut: &mut Schroeder<T>, start: usize, num_samples: usize) -> f64 {
+        max_step(start, num_samples, |input| [uut.process_wet(T::from_f64(input)).to_f64()])
+    }
+
+    fn size_change_is_glitch_free<T: Float>() {
+        let sample_rate = 44100.0;
+        for size in [SIZE_MIN, SIZE_MAX] {
+            let mut uut = Schroeder::<T>::new(sample_rate);
+            uut.prepare(sample_rate, 2000.0);
+            uut.set_dampening(0.5);
+
+            let before = wet_max_step(&mut uut, 0, 88200);
+            // Jumps straight to the new size; the delays crossfade on their own,
+            // so the output moves no faster than it does at either size.
+            uut.set_size(size);
+            let during = wet_max_step(&mut uut, 88200, 44100);
+            let after = wet_max_step(&mut uut, 132300, 88200);
+
+            assert!(during < 1.5 * before.max(after), "{}: {} vs {} / {}", size, during, before, after);
+        }
+    }
+
+    #[test]
+    fn test_size_change_is_glitch_free() {
+        size_change_is_glitch_free::<f32>();
+        size_change_is_glitch_free::<f64>();
+    }
+
+    fn block_matches_per_sample<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut per_sample = Schroeder::<T>::new(sample_rate);
+        let mut block = Schroeder::<T>::new(sample_rate);
+        for uut in [&mut per_sample, &mut block] {
+            uut.prepare(sample_rate, 2000.0);
+            uut.set_dampening(0.5);
+            uut.set_mod_enabled(true);
+            uut.set_comb_modulation(1.0, 0.5);
+        }
+
+        let mut seed: u32 = 1;
+        // Odd block lengths, so blocks straddle the internal chunks, with a
+        // size change and a freeze ramp along the way.
+        for (index, length) in [1, 100, 63, 257, 1000, 4410, 3000].into_iter().enumerate() {
+            if index == 3 {
+                per_sample.set_size(0.5);
+                block.set_size(0.5);
+            }
+            if index == 5 {
+                per_sample.set_freeze(true);
+                block.set_freeze(true);
+            }
+            let input: Vec<T> = (0..length).map(|_| noise(&mut seed)).collect();
+            let expected: Vec<T> = input.iter().map(|input| per_sample.process(*input)).collect();
+            let mut output = input.clone();
+            block.process_block_in_place(&mut output);
+            assert_eq!(output, expected, "block {}", index);
+        }
+    }
+
+    #[test]
+    fn test_block_matches_per_sample() {
+        block_matches_per_sample::<f32>();
+        block_matches_per_sample::<f64>();
+    }
+
+    fn decay_time_independent_of_sample_rate<T: Float>() {
+        let rt60_ms = 1000.0;
+        let reference = measure_rt60_s::<T>(44100.0, rt60_ms);
+        for sample_rate in [48000.0, 96000.0, 192000.0] {
+            let rt60_s = measure_rt60_s::<T>(sample_rate, rt60_ms);
+            assert!((rt60_s - reference).abs() / reference < 0.05, "{} Hz: {} s vs {} s", sample_rate, rt60_s, reference);
+        }
+    }
+
+    #[test]
+    fn test_decay_time_independent_of_sample_rate() {
+        decay_time_independent_of_sample_rate::<f32>();
+        decay_time_independent_of_sample_rate::<f64>();
+    }
+
+    fn band_decay_times<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut uut = Schroeder::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_crossovers(200.0, 6000.0);
+        uut.set_band_decay(1.5, 0.5);
+
+        let impulse_response: Vec<f64> = (0..(sample_rate * 2.5) as usize)
+            .map(|n| uut.process_wet(if n == 0 { T::ONE } else { T::ZERO }).to_f64())
+            .collect();
+
+        for (band_hz, target_s) in [((40.0, 80.0), 3.0), ((1000.0, 2000.0), 2.0), ((14000.0, 18000.0), 1.0)] {
+            let rt60_s = measure_band_rt60_s(&impulse_response, sample_rate, band_hz);
+            assert!((rt60_s - target_s).abs() / target_s < 0.15, "{:?}: {} s vs {} s", band_hz, rt60_s, target_s);
+        }
+    }
+
+    #[test]
+    fn test_band_decay_times() {
+        band_decay_times::<f32>();
+        band_decay_times::<f64>();
     }
 }
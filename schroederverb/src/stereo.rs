@@ -0,0 +1,790 @@
+use std::f64::consts::FRAC_PI_2;
+use euterpe_rs::processor::AudioProcessor;
+use crate::biquad::Biquad;
+use crate::block::MAX_BLOCK_SIZE;
+use crate::delay_line::{DelayCrossfade, DelayLine};
+use crate::ducker::Ducker;
+use crate::early_reflections::{EarlyReflections, Room, ER_MAX_DELAY_MS};
+use crate::fdn::{Fdn, MixingMatrix};
+use crate::float::Float;
+use crate::lfo::Waveform;
+use crate::freeverb::Freeverb;
+use crate::moorer::Moorer;
+use crate::reverb::{
+    get_length_in_samples, Algorithm, Reverb, DEFAULT_HIGH_CROSSOVER_HZ, DEFAULT_LOW_CROSSOVER_HZ,
+};
+use crate::schroeder::{Schroeder, FREEZE_RAMP_MS};
+use crate::tempo::MIN_PRE_DELAY_SYNC_TEMPO_BPM;
+
+const STEREO_SPREAD_MAX_MS: f64 = 1.0;
+/// A synced quarter note at the slowest tempo the sync supports.
+const PRE_DELAY_MAX_MS: f64 = 60_000.0 / MIN_PRE_DELAY_SYNC_TEMPO_BPM;
+const ALGORITHM_CROSSFADE_MS: f64 = 50.0;
+const RIGHT: usize = 1;
+const FDN_SIZE: usize = 8;
+const TILT_PIVOT_HZ: f64 = 1000.0;
+
+/// Every reverb network for one channel, behind a shared pre-delay, and the
+/// EQ on the channel's wet signal. Only the selected network, and the one
+/// fading out after a switch, are processed.
+struct ReverbChannel<T: Float> {
+    pre_delay: DelayLine<T>,
+    /// Read position of `pre_delay`, which crossfades when the time changes.
+    pre_delay_tap: DelayCrossfade,
+    low_cut: Biquad<T>,
+    high_cut: Biquad<T>,
+    tilt_low: Biquad<T>,
+    tilt_high: Biquad<T>,
+    schroeder: Schroeder<T>,
+    moorer: Moorer<T>,
+    freeverb: Freeverb<T>,
+    fdn: Fdn<T, FDN_SIZE>,
+}
+
+impl<T: Float> ReverbChannel<T> {
+    fn new(sample_rate: f64) -> Self {
+        let pre_delay_length = get_length_in_samples(PRE_DELAY_MAX_MS, sample_rate).ceil() as usize;
+
+        ReverbChannel {
+            pre_delay: DelayLine::new(pre_delay_length),
+            pre_delay_tap: DelayCrossfade::new(0.0),
+            low_cut: Biquad::new(),
+            high_cut: Biquad::new(),
+            tilt_low: Biquad::new(),
+            tilt_high: Biquad::new(),
+            schroeder: Schroeder::new(sample_rate),
+            moorer: Moorer::new(sample_rate),
+            freeverb: Freeverb::new(sample_rate),
+            fdn: Fdn::new(sample_rate, MixingMatrix::Hadamard),
+        }
+    }
+
+    fn engine(&mut self, algorithm: Algorithm) -> &mut dyn Reverb<T> {
+        match algorithm {
+            Algorithm::Schroeder => &mut self.schroeder,
+            Algorithm::Moorer => &mut self.moorer,
+            Algorithm::Freeverb => &mut self.freeverb,
+            Algorithm::Fdn => &mut self.fdn,
+        }
+    }
+
+    fn delay(&mut self, input: T) -> T {
+        self.pre_delay.write(input);
+        let line = &mut self.pre_delay;
+        self.pre_delay_tap.read(|delay, head| line.read_head(delay, head), 0.0)
+    }
+
+    fn equalize(&mut self, wet: T) -> T {
+        let wet = self.high_cut.process(self.low_cut.process(wet));
+        self.tilt_high.process(self.tilt_low.process(wet))
+    }
+}
+
+/// Stereo reverb built from one network per channel. The right network's
+/// delays are offset by the stereo spread so the two tails decorrelate. The
+/// wet signal is then widened or narrowed in mid/side before being mixed with
+/// the dry input. Switching algorithms crossfades from the old network to the
+/// new one. Early reflections of the summed, pre-delayed input are blended
+/// with the tails before the width stage.
+pub struct StereoReverb<T: Float = f64> {
+    channels: [ReverbChannel<T>; 2],
+    early_reflections: EarlyReflections<T>,
+    er_balance: f64,
+    /// Gain on the input of the early reflections, ramps down to 0.0 while
+    /// the Schroeder tail is frozen so new input is not heard through them.
+    early_input_gain: f64,
+    frozen: bool,
+    ducker: Ducker<T>,
+    low_cut_hz: f64,
+    high_cut_hz: f64,
+    tilt_db: f64,
+    algorithm: Algorithm,
+    fading_out: Option<Algorithm>,
+    fade_position: f64,
+    fade_step: f64,
+    sample_rate: f64,
+    rt60_ms: f64,
+    dampening: f64,
+    delay_offset_ms: f64,
+    pre_delay_ms: f64,
+    /// Low and high band decay multipliers.
+    band_decay: (f64, f64),
+    crossovers_hz: (f64, f64),
+    width: f64,
+    dry_wet_mix: f64,
+}
+
+impl<T: Float> StereoReverb<T> {
+    pub fn new(sample_rate: f64) -> Self {
+        StereoReverb {
+            channels: [ReverbChannel::new(sample_rate), ReverbChannel::new(sample_rate)],
+            early_reflections: EarlyReflections::new(sample_rate),
+            er_balance: 0.0,
+            early_input_gain: 1.0,
+            frozen: false,
+            ducker: Ducker::new(sample_rate),
+            low_cut_hz: 0.0,
+            high_cut_hz: 0.0,
+            tilt_db: 0.0,
+            algorithm: Algorithm::Schroeder,
+            fading_out: None,
+            fade_position: 0.0,
+            fade_step: 1.0 / get_length_in_samples(ALGORITHM_CROSSFADE_MS, sample_rate),
+            sample_rate,
+            rt60_ms: 0.0,
+            dampening: 0.0,
+            delay_offset_ms: 0.0,
+            pre_delay_ms: 0.0,
+            band_decay: (1.0, 1.0),
+            crossovers_hz: (DEFAULT_LOW_CROSSOVER_HZ, DEFAULT_HIGH_CROSSOVER_HZ),
+            width: 1.0,
+            dry_wet_mix: 0.5,
+        }
+    }
+
+    pub fn prepare(&mut self, sample_rate: f64, rt60_ms: f64) {
+        self.sample_rate = sample_rate;
+        self.rt60_ms = rt60_ms;
+        self.fade_step = 1.0 / get_length_in_samples(ALGORITHM_CROSSFADE_MS, sample_rate);
+
+        for channel in self.channels.iter_mut() {
+            for algorithm in [Algorithm::Schroeder, Algorithm::Moorer, Algorithm::Freeverb, Algorithm::Fdn] {
+                channel.engine(algorithm).prepare(sample_rate, rt60_ms);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.fading_out = None;
+        self.early_reflections.reset();
+        self.early_input_gain = if self.is_frozen() { 0.0 } else { 1.0 };
+        self.ducker.reset();
+        for channel in self.channels.iter_mut() {
+            channel.pre_delay.reset();
+            channel.pre_delay_tap.settle();
+            for filter in [&mut channel.low_cut, &mut channel.high_cut, &mut channel.tilt_low, &mut channel.tilt_high] {
+                filter.reset();
+            }
+            channel.engine(self.algorithm).reset();
+        }
+    }
+
+    /// Starts a crossfade to `algorithm`. A switch requested while a
+    /// crossfade is still running is ignored, so callers should keep passing
+    /// the wanted algorithm until it takes effect.
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+        if algorithm == self.algorithm || self.fading_out.is_some() {
+            return;
+        }
+
+        // The incoming network was idle, so it is cleared of whatever tail it
+        // held and brought up to date with the settings it missed.
+        let (rt60_ms, dampening, delay_offset_ms) = (self.rt60_ms, self.dampening, self.delay_offset_ms);
+        let (band_decay, crossovers_hz) = (self.band_decay, self.crossovers_hz);
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            let engine = channel.engine(algorithm);
+            engine.reset();
+            engine.update_reverb_time(rt60_ms);
+            engine.set_dampening(dampening);
+            engine.set_band_decay(band_decay.0, band_decay.1);
+            engine.set_crossovers(crossovers_hz.0, crossovers_hz.1);
+            engine.set_delay_offset(if index == RIGHT { delay_offset_ms } else { 0.0 });
+        }
+
+        self.fading_out = Some(self.algorithm);
+        self.algorithm = algorithm;
+        self.fade_position = 0.0;
+    }
+
+    fn for_each_active_engine(&mut self, mut update: impl FnMut(&mut dyn Reverb<T>, usize)) {
+        let algorithms = [Some(self.algorithm), self.fading_out];
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            for algorithm in algorithms.into_iter().flatten() {
+                update(channel.engine(algorithm), index);
+            }
+        }
+    }
+
+    /// `spread` goes from 0.0 (identical delay sets) to 1.0 (right delays
+    /// shifted by `STEREO_SPREAD_MAX_MS`).
+    pub fn set_stereo_spread(&mut self, spread: f64) {
+        let delay_offset_ms = spread.clamp(0.0, 1.0) * STEREO_SPREAD_MAX_MS;
+        self.delay_offset_ms = delay_offset_ms;
+        self.for_each_active_engine(|engine, index| {
+            if index == RIGHT {
+                engine.set_delay_offset(delay_offset_ms);
+            }
+        });
+    }
+
+    /// Delay between the dry signal and the onset of the reverb, up to a
+    /// quarter note at `MIN_PRE_DELAY_SYNC_TEMPO_BPM`. Changes crossfade to the
+    /// new time.
+    pub fn set_pre_delay(&mut self, pre_delay_ms: f64) {
+        self.pre_delay_ms = pre_delay_ms.clamp(0.0, PRE_DELAY_MAX_MS);
+        let delay_samples = get_length_in_samples(self.pre_delay_ms, self.sample_rate).round();
+        for channel in self.channels.iter_mut() {
+            channel.pre_delay_tap.set_target(delay_samples);
+        }
+    }
+
+    /// The pre-delay as applied, after clamping.
+    pub fn pre_delay_ms(&self) -> f64 {
+        self.pre_delay_ms
+    }
+
+    /// Longest the wet output can stay silent while an input is still on its
+    /// way through: the pre-delay, the last early reflection and the longest
+    /// loop of any network.
+    pub fn max_silent_gap_ms(&self) -> f64 {
+        let channel = &self.channels[0];
+        let networks: [&dyn Reverb<T>; 4] = [&channel.schroeder, &channel.moorer, &channel.freeverb, &channel.fdn];
+        let network_ms = networks.iter().map(|network| network.max_delay_ms()).fold(0.0, f64::max);
+        self.pre_delay_ms + ER_MAX_DELAY_MS + network_ms
+    }
+
+    /// Time from the end of the input until the tail has decayed by 60 dB in
+    /// its slowest band.
+    pub fn tail_length_ms(&self) -> f64 {
+        // The high band never outlasts the mids.
+        self.rt60_ms * self.band_decay.0.max(1.0) + self.max_silent_gap_ms()
+    }
+
+    pub fn set_diffusion(&mut self, diffusion: f64) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_diffusion(diffusion);
+        }
+    }
+
+    /// Low and high band decay times, as multiples of the RT60.
+    pub fn set_band_decay(&mut self, low_multiplier: f64, high_multiplier: f64) {
+        self.band_decay = (low_multiplier, high_multiplier);
+        self.for_each_active_engine(|engine, _| engine.set_band_decay(low_multiplier, high_multiplier));
+    }
+
+    pub fn set_crossovers(&mut self, low_hz: f64, high_hz: f64) {
+        self.crossovers_hz = (low_hz, high_hz);
+        self.for_each_active_engine(|engine, _| engine.set_crossovers(low_hz, high_hz));
+    }
+
+    pub fn set_dampening(&mut self, dampening: f64) {
+        self.dampening = dampening;
+        self.for_each_active_engine(|engine, _| engine.set_dampening(dampening));
+    }
+
+    pub fn set_room(&mut self, room: Room) {
+        self.early_reflections.set_room(room);
+    }
+
+    /// 0.0 is the late tail only, 1.0 the early reflections only, with an
+    /// equal-power blend in between.
+    pub fn set_er_balance(&mut self, er_balance: f64) {
+        self.er_balance = er_balance.clamp(0.0, 1.0);
+    }
+
+    /// High-pass on the wet signal, the usual low cut on a reverb return.
+    pub fn set_low_cut(&mut self, cutoff_hz: f64) {
+        if cutoff_hz == self.low_cut_hz {
+            return;
+        }
+        self.low_cut_hz = cutoff_hz;
+        for channel in self.channels.iter_mut() {
+            channel.low_cut.design_high_pass(cutoff_hz, self.sample_rate);
+        }
+    }
+
+    /// Low-pass on the wet signal.
+    pub fn set_high_cut(&mut self, cutoff_hz: f64) {
+        if cutoff_hz == self.high_cut_hz {
+            return;
+        }
+        self.high_cut_hz = cutoff_hz;
+        for channel in self.channels.iter_mut() {
+            channel.high_cut.design_low_pass(cutoff_hz, self.sample_rate);
+        }
+    }
+
+    /// Tilts the wet spectrum around 1 kHz: positive values brighten it by
+    /// `tilt_db` from the lows to the highs, negative values darken it.
+    pub fn set_tilt(&mut self, tilt_db: f64) {
+        if tilt_db == self.tilt_db {
+            return;
+        }
+        self.tilt_db = tilt_db;
+        for channel in self.channels.iter_mut() {
+            channel.tilt_low.design_low_shelf(TILT_PIVOT_HZ, -0.5 * tilt_db, self.sample_rate);
+            channel.tilt_high.design_high_shelf(TILT_PIVOT_HZ, 0.5 * tilt_db, self.sample_rate);
+        }
+    }
+
+    /// Envelope settings of the ducker, see `Ducker`.
+    pub fn set_ducking(&mut self, amount: f64, attack_ms: f64, release_ms: f64) {
+        self.ducker.set_amount(amount);
+        self.ducker.set_attack(attack_ms);
+        self.ducker.set_release(release_ms);
+    }
+
+    pub fn set_dry_wet_mix(&mut self, dry_wet_mix: f64) {
+        self.dry_wet_mix = dry_wet_mix;
+    }
+
+    /// Side gain of the wet signal: 0.0 is mono, 1.0 leaves the tail
+    /// untouched and 2.0 doubles the side component.
+    pub fn set_width(&mut self, width: f64) {
+        self.width = width;
+    }
+
+    /// Per-comb delay modulation of the Schroeder network, see
+    /// `Schroeder::set_comb_modulation`.
+    pub fn set_comb_modulation(&mut self, depth_ms: f64, rate_hz: f64) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_comb_modulation(depth_ms, rate_hz);
+        }
+    }
+
+    /// Scales the Schroeder delays, see `Schroeder::set_size`.
+    pub fn set_size(&mut self, size: f64) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_size(size);
+        }
+    }
+
+    /// Holds the Schroeder tail indefinitely, see `Schroeder::set_freeze`.
+    /// The other networks do not freeze.
+    pub fn set_freeze(&mut self, frozen: bool) {
+        self.frozen = frozen;
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_freeze(frozen);
+        }
+    }
+
+    /// Whether a tail is being held, which only the Schroeder network does.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen && self.algorithm == Algorithm::Schroeder
+    }
+
+    pub fn set_mod_enabled(&mut self, enabled: bool) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_mod_enabled(enabled);
+        }
+    }
+
+    pub fn set_mod_lfo_freq(&mut self, freq: f32) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_mod_lfo_freq(freq);
+        }
+    }
+
+    pub fn set_mod_depth(&mut self, depth_ms: f64) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_mod_depth(depth_ms);
+        }
+    }
+
+    pub fn set_mod_waveform(&mut self, waveform: Waveform) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_mod_waveform(waveform);
+        }
+    }
+
+    pub fn set_mod_lfo_phase(&mut self, phase: f64) {
+        for channel in self.channels.iter_mut() {
+            channel.schroeder.set_mod_lfo_phase(phase);
+        }
+    }
+
+    pub fn update_reverb_time(&mut self, rt60_ms: f64) {
+        self.rt60_ms = rt60_ms;
+        self.for_each_active_engine(|engine, _| engine.update_reverb_time(rt60_ms));
+    }
+
+    /// Processes one stereo sample, ducking the wet signal by the dry input.
+    pub fn process(&mut self, left: T, right: T) -> (T, T) {
+        self.process_with_key(left, right, left.abs().max(right.abs()))
+    }
+
+    /// Processes one stereo sample, ducking the wet signal by `key`.
+    pub fn process_with_key(&mut self, left: T, right: T, key: T) -> (T, T) {
+        let (fade_in, fade_out) = self.fade_gains();
+
+        let mut wet = [T::ZERO; 2];
+        let mut delayed = [T::ZERO; 2];
+        for (index, (channel, input)) in self.channels.iter_mut().zip([left, right]).enumerate() {
+            let input = channel.delay(input);
+            delayed[index] = input;
+            wet[index] = channel.engine(self.algorithm).process_wet(input) * fade_in;
+            if let Some(previous) = self.fading_out {
+                wet[index] += channel.engine(previous).process_wet(input) * fade_out;
+            }
+        }
+
+        self.mix(left, right, delayed, wet, key)
+    }
+
+    /// Block version of `process_with_key`, processing `left` and `right` in
+    /// place. The pre-delays and networks run a block at a time, the rest
+    /// sample by sample.
+    pub fn process_block(&mut self, left: &mut [T], right: &mut [T], key: &[T]) {
+        let chunks = left.chunks_mut(MAX_BLOCK_SIZE).zip(right.chunks_mut(MAX_BLOCK_SIZE)).zip(key.chunks(MAX_BLOCK_SIZE));
+        for ((left, right), key) in chunks {
+            let len = left.len();
+            let mut delayed = [[T::ZERO; MAX_BLOCK_SIZE]; 2];
+            let mut wet = [[T::ZERO; MAX_BLOCK_SIZE]; 2];
+            let mut fading_wet = [[T::ZERO; MAX_BLOCK_SIZE]; 2];
+            for (index, (channel, input)) in self.channels.iter_mut().zip([&*left, &*right]).enumerate() {
+                let delayed = &mut delayed[index][..len];
+                for (input, delayed) in input.iter().zip(delayed.iter_mut()) {
+                    *delayed = channel.delay(*input);
+                }
+                channel.engine(self.algorithm).process_wet_block(delayed, &mut wet[index][..len]);
+                if let Some(previous) = self.fading_out {
+                    channel.engine(previous).process_wet_block(delayed, &mut fading_wet[index][..len]);
+                }
+            }
+
+            for n in 0..len {
+                // The crossfade can end part way through the block, from then
+                // on the outgoing network's output is dropped.
+                let (fade_in, fade_out) = self.fade_gains();
+                let mut wet = [wet[0][n] * fade_in, wet[1][n] * fade_in];
+                if self.fading_out.is_some() {
+                    wet[0] += fading_wet[0][n] * fade_out;
+                    wet[1] += fading_wet[1][n] * fade_out;
+                }
+                (left[n], right[n]) = self.mix(left[n], right[n], [delayed[0][n], delayed[1][n]], wet, key[n]);
+            }
+        }
+    }
+
+    /// Equal-power crossfade gains of the incoming and outgoing networks, the
+    /// two tails are uncorrelated.
+    fn fade_gains(&self) -> (T, T) {
+        match self.fading_out {
+            Some(_) => {
+                let angle = self.fade_position * FRAC_PI_2;
+                (T::from_f64(angle.sin()), T::from_f64(angle.cos()))
+            }
+            None => (T::ONE, T::ZERO),
+        }
+    }
+
+    /// Moves the early reflections' input gain one sample towards the freeze
+    /// state, at the rate the Schroeder network mutes its own input.
+    fn advance_freeze(&mut self) {
+        let target = if self.is_frozen() { 0.0 } else { 1.0 };
+        if self.early_input_gain == target {
+            return;
+        }
+        let step = 1.0 / get_length_in_samples(FREEZE_RAMP_MS, self.sample_rate);
+        self.early_input_gain = if target > self.early_input_gain {
+            (self.early_input_gain + step).min(1.0)
+        } else {
+            (self.early_input_gain - step).max(0.0)
+        };
+    }
+
+    /// Everything after the networks for one sample: blends in the early
+    /// reflections of the pre-delayed input, equalizes, advances the
+    /// crossfade, ducks, sets the width and mixes with the dry input.
+    fn mix(&mut self, left: T, right: T, delayed: [T; 2], mut wet: [T; 2], key: T) -> (T, T) {
+        self.advance_freeze();
+        let half = T::from_f64(0.5);
+        let early_input = half * (delayed[0] + delayed[1]) * T::from_f64(self.early_input_gain);
+        let (early_left, early_right) = self.early_reflections.process(early_input);
+        let angle = self.er_balance * FRAC_PI_2;
+        let (early_gain, late_gain) = (T::from_f64(angle.sin()), T::from_f64(angle.cos()));
+        wet[0] = wet[0] * late_gain + early_left * early_gain;
+        wet[1] = wet[1] * late_gain + early_right * early_gain;
+        for (wet, channel) in wet.iter_mut().zip(self.channels.iter_mut()) {
+            *wet = channel.equalize(*wet);
+        }
+
+        if self.fading_out.is_some() {
+            self.fade_position += self.fade_step;
+            if self.fade_position >= 1.0 {
+                self.fading_out = None;
+            }
+        }
+
+        let duck_gain = self.ducker.process(key);
+        let mid = half * (wet[0] + wet[1]) * duck_gain;
+        let side = half * (wet[0] - wet[1]) * T::from_f64(self.width) * duck_gain;
+
+        let (wet_gain, dry_gain) = (T::from_f64(self.dry_wet_mix), T::from_f64(1.0 - self.dry_wet_mix));
+        ((mid + side) * wet_gain + left * dry_gain, (mid - side) * wet_gain + right * dry_gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{count_allocations, max_step, noise};
+
+    fn spread_decorrelates_channels<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut uut = StereoReverb::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_dry_wet_mix(1.0);
+
+        uut.set_stereo_spread(0.0);
+        let (left, right) = uut.process(T::ONE, T::ONE);
+        assert_eq!(left, right);
+        for _ in 0..4410 {
+            let (left, right) = uut.process(T::ZERO, T::ZERO);
+            assert_eq!(left, right);
+        }
+
+        let mut uut = StereoReverb::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_dry_wet_mix(1.0);
+        uut.set_stereo_spread(1.0);
+        uut.process(T::ONE, T::ONE);
+        let differs = (0..4410).any(|_| {
+            let (left, right) = uut.process(T::ZERO, T::ZERO);
+            left != right
+        });
+        assert!(differs);
+    }
+
+    #[test]
+    fn test_spread_decorrelates_channels() {
+        spread_decorrelates_channels::<f32>();
+        spread_decorrelates_channels::<f64>();
+    }
+
+    fn wet_correlation<T: Float>(width: f64) -> f64 {
+        let sample_rate = 44100.0;
+        let mut uut = StereoReverb::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_dry_wet_mix(1.0);
+        uut.set_stereo_spread(1.0);
+        uut.set_width(width);
+
+        let mut seed: u32 = 1;
+        let (mut sum_lr, mut sum_ll, mut sum_rr) = (0.0, 0.0, 0.0);
+        for _ in 0..44100 {
+            let input = noise(&mut seed);
+            let (left, right) = uut.process(input, input);
+            let (left, right) = (left.to_f64(), right.to_f64());
+            sum_lr += left * right;
+            sum_ll += left * left;
+            sum_rr += right * right;
+        }
+        sum_lr / (sum_ll * sum_rr).sqrt()
+    }
+
+    fn width_correlation<T: Float>() {
+        let mono = wet_correlation::<T>(0.0);
+        let normal = wet_correlation::<T>(1.0);
+        let wide = wet_correlation::<T>(2.0);
+
+        assert!((mono - 1.0).abs() < 1e-9);
+        assert!(normal < 0.99);
+        assert!(wide < normal);
+    }
+
+    #[test]
+    fn test_width_correlation() {
+        width_correlation::<f32>();
+        width_correlation::<f64>();
+    }
+
+    fn freeze_mutes_early_reflections_input<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut uut = StereoReverb::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_dry_wet_mix(1.0);
+        uut.set_er_balance(1.0);
+        uut.set_freeze(true);
+        assert!(uut.is_frozen());
+
+        // Once the ramp and the last reflection have passed, the input keeps
+        // coming but nothing of it reaches the output.
+        let settle = get_length_in_samples(FREEZE_RAMP_MS + ER_MAX_DELAY_MS, sample_rate) as usize;
+        let mut seed: u32 = 1;
+        for n in 0..settle + 4410 {
+            let input = noise(&mut seed);
+            let (left, right) = uut.process(input, input);
+            if n > settle {
+                assert!(left.to_f64().abs() < 1e-9 && right.to_f64().abs() < 1e-9, "{:?} {:?}", left, right);
+            }
+        }
+
+        uut.set_algorithm(Algorithm::Fdn);
+        assert!(!uut.is_frozen());
+    }
+
+    #[test]
+    fn test_freeze_mutes_early_reflections_input() {
+        freeze_mutes_early_reflections_input::<f32>();
+        freeze_mutes_early_reflections_input::<f64>();
+    }
+
+    /// Largest sample-to-sample step of either output over `num_samples` of a
+    /// steady sine.
+    fn stereo_max_step<T: Float>(uut: &mut StereoReverb<T>, start: usize, num_samples: usize) -> f64 {
+        max_step(start, num_samples, |input| {
+            let (left, right) = uut.process(T::from_f64(input), T::from_f64(input));
+            [left.to_f64(), right.to_f64()]
+        })
+    }
+
+    fn algorithm_switch_is_click_free<T: Float>() {
+        let sample_rate = 44100.0;
+        for algorithm in [Algorithm::Moorer, Algorithm::Freeverb, Algorithm::Fdn] {
+            let mut uut = StereoReverb::<T>::new(sample_rate);
+            uut.prepare(sample_rate, 2000.0);
+            uut.set_dampening(0.5);
+            uut.set_dry_wet_mix(1.0);
+
+            let before = stereo_max_step(&mut uut, 0, 88200);
+            uut.set_algorithm(algorithm);
+            let during = stereo_max_step(&mut uut, 88200, 4410);
+            let after = stereo_max_step(&mut uut, 92610, 88200);
+
+            assert!(during <= 1.1 * before.max(after), "{:?}: {} vs {} / {}", algorithm, during, before, after);
+        }
+    }
+
+    #[test]
+    fn test_algorithm_switch_is_click_free() {
+        algorithm_switch_is_click_free::<f32>();
+        algorithm_switch_is_click_free::<f64>();
+    }
+
+    fn spread_change_is_click_free<T: Float>() {
+        let sample_rate = 44100.0;
+        for algorithm in [Algorithm::Schroeder, Algorithm::Moorer, Algorithm::Freeverb, Algorithm::Fdn] {
+            let mut uut = StereoReverb::<T>::new(sample_rate);
+            uut.prepare(sample_rate, 2000.0);
+            uut.set_dampening(0.5);
+            uut.set_dry_wet_mix(1.0);
+            uut.set_algorithm(algorithm);
+
+            let before = stereo_max_step(&mut uut, 0, 88200);
+            // The right channel's delays move by the whole spread at once.
+            uut.set_stereo_spread(1.0);
+            let during = stereo_max_step(&mut uut, 88200, 4410);
+            let after = stereo_max_step(&mut uut, 92610, 88200);
+
+            assert!(during <= 1.1 * before.max(after), "{:?}: {} vs {} / {}", algorithm, during, before, after);
+        }
+    }
+
+    #[test]
+    fn test_spread_change_is_click_free() {
+        spread_change_is_click_free::<f32>();
+        spread_change_is_click_free::<f64>();
+    }
+
+    fn switch_and_reset_do_not_allocate<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut uut = StereoReverb::<T>::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        for algorithm in [Algorithm::Moorer, Algorithm::Freeverb, Algorithm::Fdn, Algorithm::Schroeder] {
+            // Both are called from the audio thread.
+            let allocations = count_allocations(|| {
+                uut.set_algorithm(algorithm);
+                uut.reset();
+            });
+            assert_eq!(allocations, 0, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_switch_and_reset_do_not_allocate() {
+        switch_and_reset_do_not_allocate::<f32>();
+        switch_and_reset_do_not_allocate::<f64>();
+    }
+
+    /// The whole chain engaged: pre-delay, early reflections, EQ and ducking.
+    fn configured<T: Float>(sample_rate: f64) -> StereoReverb<T> {
+        let mut uut = StereoReverb::new(sample_rate);
+        uut.prepare(sample_rate, 2000.0);
+        uut.set_pre_delay(20.0);
+        uut.set_er_balance(0.3);
+        uut.set_low_cut(100.0);
+        uut.set_high_cut(8000.0);
+        uut.set_tilt(-2.0);
+        uut.set_ducking(0.5, 10.0, 250.0);
+        uut.set_stereo_spread(0.5);
+        uut.set_dry_wet_mix(1.0);
+        uut
+    }
+
+    fn reset_clears_everything<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut uut = configured::<T>(sample_rate);
+        let mut seed: u32 = 1;
+        for _ in 0..22050 {
+            let input = noise(&mut seed);
+            uut.process(input, input);
+        }
+        // Cut off part way through a crossfade.
+        uut.set_algorithm(Algorithm::Fdn);
+        for _ in 0..1000 {
+            let input = noise(&mut seed);
+            uut.process(input, input);
+        }
+        uut.reset();
+
+        for n in 0..22050 {
+            assert_eq!(uut.process(T::ZERO, T::ZERO), (T::ZERO, T::ZERO), "sample {}", n);
+        }
+
+        // Nothing from before the reset is left to tell it apart from a
+        // fresh instance.
+        let mut reference = configured::<T>(sample_rate);
+        reference.set_algorithm(Algorithm::Fdn);
+        reference.reset();
+        let mut seed: u32 = 2;
+        for n in 0..22050 {
+            let input = noise(&mut seed);
+            assert_eq!(uut.process(input, input), reference.process(input, input), "sample {}", n);
+        }
+    }
+
+    #[test]
+    fn test_reset_clears_everything() {
+        reset_clears_everything::<f32>();
+        reset_clears_everything::<f64>();
+    }
+
+    fn block_matches_per_sample<T: Float>() {
+        let sample_rate = 44100.0;
+        let mut per_sample = StereoReverb::<T>::new(sample_rate);
+        let mut block = StereoReverb::<T>::new(sample_rate);
+        for uut in [&mut per_sample, &mut block] {
+            uut.prepare(sample_rate, 2000.0);
+            uut.set_stereo_spread(0.5);
+            uut.set_er_balance(0.3);
+            uut.set_ducking(0.5, 10.0, 200.0);
+        }
+
+        let mut seed: u32 = 1;
+        let mut next_noise = || noise::<T>(&mut seed);
+        // The switch crossfade ends part way through a block.
+        for (index, length) in [100, 1000, 4410, 333, 2000].into_iter().enumerate() {
+            if index == 1 {
+                per_sample.set_algorithm(Algorithm::Fdn);
+                block.set_algorithm(Algorithm::Fdn);
+            }
+            let mut left: Vec<T> = (0..length).map(|_| next_noise()).collect();
+            let mut right: Vec<T> = (0..length).map(|_| next_noise()).collect();
+            let key: Vec<T> = (0..length).map(|_| next_noise().abs()).collect();
+
+            let expected: Vec<(T, T)> = (0..length)
+                .map(|n| per_sample.process_with_key(left[n], right[n], key[n]))
+                .collect();
+            block.process_block(&mut left, &mut right, &key);
+            let output: Vec<(T, T)> = left.into_iter().zip(right).collect();
+            assert_eq!(output, expected, "block {}", index);
+        }
+    }
+
+    #[test]
+    fn test_block_matches_per_sample() {
+        block_matches_per_sample::<f32>();
+        block_matches_per_sample::<f64>();
+    }
+}
@@ -0,0 +1,142 @@
+use nih_plug::prelude::Enum;
+
+/// Note lengths the tempo-synced parameters can lock to.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteDivision {
+    #[name = "1/1"]
+    Whole,
+    #[name = "1/2"]
+    Half,
+    #[name = "1/2 D"]
+    HalfDotted,
+    #[name = "1/2 T"]
+    HalfTriplet,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/4 D"]
+    QuarterDotted,
+    #[name = "1/4 T"]
+    QuarterTriplet,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/8 D"]
+    EighthDotted,
+    #[name = "1/8 T"]
+    EighthTriplet,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/16 D"]
+    SixteenthDotted,
+    #[name = "1/16 T"]
+    SixteenthTriplet,
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+/// Slowest tempo the synced pre-delay fits the pre-delay line at. Below it
+/// the pre-delay stops growing.
+pub const MIN_PRE_DELAY_SYNC_TEMPO_BPM: f64 = 40.0;
+
+/// The note lengths the synced pre-delay offers: a quarter note and shorter,
+/// so each one fits the pre-delay line down to `MIN_PRE_DELAY_SYNC_TEMPO_BPM`.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreDelayDivision {
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/4 T"]
+    QuarterTriplet,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/8 D"]
+    EighthDotted,
+    #[name = "1/8 T"]
+    EighthTriplet,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/16 D"]
+    SixteenthDotted,
+    #[name = "1/16 T"]
+    SixteenthTriplet,
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl PreDelayDivision {
+    pub fn note(self) -> NoteDivision {
+        match self {
+            PreDelayDivision::Quarter => NoteDivision::Quarter,
+            PreDelayDivision::QuarterTriplet => NoteDivision::QuarterTriplet,
+            PreDelayDivision::Eighth => NoteDivision::Eighth,
+            PreDelayDivision::EighthDotted => NoteDivision::EighthDotted,
+            PreDelayDivision::EighthTriplet => NoteDivision::EighthTriplet,
+            PreDelayDivision::Sixteenth => NoteDivision::Sixteenth,
+            PreDelayDivision::SixteenthDotted => NoteDivision::SixteenthDotted,
+            PreDelayDivision::SixteenthTriplet => NoteDivision::SixteenthTriplet,
+            PreDelayDivision::ThirtySecond => NoteDivision::ThirtySecond,
+        }
+    }
+}
+
+impl NoteDivision {
+    /// Length in quarter-note beats.
+    pub fn beats(self) -> f64 {
+        let (quarters, modifier) = match self {
+            NoteDivision::Whole => (4.0, 1.0),
+            NoteDivision::Half => (2.0, 1.0),
+            NoteDivision::HalfDotted => (2.0, 1.5),
+            NoteDivision::HalfTriplet => (2.0, 2.0 / 3.0),
+            NoteDivision::Quarter => (1.0, 1.0),
+            NoteDivision::QuarterDotted => (1.0, 1.5),
+            NoteDivision::QuarterTriplet => (1.0, 2.0 / 3.0),
+            NoteDivision::Eighth => (0.5, 1.0),
+            NoteDivision::EighthDotted => (0.5, 1.5),
+            NoteDivision::EighthTriplet => (0.5, 2.0 / 3.0),
+            NoteDivision::Sixteenth => (0.25, 1.0),
+            NoteDivision::SixteenthDotted => (0.25, 1.5),
+            NoteDivision::SixteenthTriplet => (0.25, 2.0 / 3.0),
+            NoteDivision::ThirtySecond => (0.125, 1.0),
+        };
+        quarters * modifier
+    }
+
+    pub fn length_ms(self, tempo_bpm: f64) -> f64 {
+        self.beats() * 60_000.0 / tempo_bpm
+    }
+
+    /// Frequency of an LFO that runs one cycle per note.
+    pub fn freq_hz(self, tempo_bpm: f64) -> f64 {
+        1000.0 / self.length_ms(tempo_bpm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_lengths() {
+        let approx_eq = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(approx_eq(NoteDivision::Quarter.length_ms(120.0), 500.0));
+        assert!(approx_eq(NoteDivision::EighthDotted.length_ms(120.0), 375.0));
+        assert!(approx_eq(NoteDivision::SixteenthTriplet.length_ms(120.0), 250.0 / 3.0));
+        assert!(approx_eq(NoteDivision::Whole.freq_hz(120.0), 0.5));
+    }
+
+    #[test]
+    fn test_pre_delay_divisions_fit_a_quarter_note() {
+        let divisions = [
+            PreDelayDivision::Quarter,
+            PreDelayDivision::QuarterTriplet,
+            PreDelayDivision::Eighth,
+            PreDelayDivision::EighthDotted,
+            PreDelayDivision::EighthTriplet,
+            PreDelayDivision::Sixteenth,
+            PreDelayDivision::SixteenthDotted,
+            PreDelayDivision::SixteenthTriplet,
+            PreDelayDivision::ThirtySecond,
+        ];
+        for division in divisions {
+            assert!(division.note().beats() <= 1.0, "{:?}", division);
+        }
+    }
+}
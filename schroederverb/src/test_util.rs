@@ -0,0 +1,71 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use crate::float::Float;
+use crate::reverb::Reverb;
+
+/// System allocator that counts the allocations made on each thread, so a
+/// test can check that a call stays off the heap while other tests run.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of heap allocations `f` makes.
+pub(crate) fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// White noise between -1.0 and 1.0, the same sequence for the same starting
+/// `seed`.
+pub(crate) fn noise<T: Float>(seed: &mut u32) -> T {
+    *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+    T::from_f64((*seed as f64 / u32::MAX as f64) * 2.0 - 1.0)
+}
+
+/// Largest sample-to-sample step of any of `process`'s outputs over
+/// `num_samples` of a steady 220 Hz sine at 44.1 kHz, starting at `start`.
+pub(crate) fn max_step<const N: usize>(start: usize, num_samples: usize, mut process: impl FnMut(f64) -> [f64; N]) -> f64 {
+    let sine = |n: usize| (2.0 * std::f64::consts::PI * 220.0 * n as f64 / 44100.0).sin();
+    let mut previous = process(sine(start));
+    (start + 1..start + num_samples)
+        .map(|n| {
+            let out = process(sine(n));
+            let step = out.iter().zip(previous.iter()).map(|(out, previous)| (out - previous).abs()).fold(0.0, f64::max);
+            previous = out;
+            step
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Checks that a second of `uut`'s impulse response at 44.1 kHz rings and
+/// has lost 30 dB by its last 100 ms.
+pub(crate) fn assert_impulse_response_decays(uut: &mut dyn Reverb) {
+    let out: Vec<f64> = (0..44100).map(|n| uut.process_wet(if n == 0 { 1.0 } else { 0.0 })).collect();
+    let energy = |range: std::ops::Range<usize>| out[range].iter().map(|x| x * x).sum::<f64>();
+
+    assert!(out.iter().all(|x| x.is_finite()));
+    assert!(energy(0..4410) > 0.0);
+    assert!(energy(39690..44100) < energy(0..4410) * 1e-3);
+}
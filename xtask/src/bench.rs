@@ -0,0 +1,113 @@
+//! Regression guard for the `dsp` benchmarks.
+//!
+//! `cargo xtask bench-baseline [name]` runs them and saves the results as a
+//! criterion baseline. `cargo xtask bench-check [name] [--threshold percent]`
+//! runs them again against it and fails if any benchmark got slower by more
+//! than the threshold, 5% unless given, with criterion's confidence.
+
+use anyhow::{bail, Context};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+const PACKAGE: &str = "schroederverb";
+const BENCH: &str = "dsp";
+const DEFAULT_BASELINE: &str = "main";
+const DEFAULT_THRESHOLD_PERCENT: f64 = 5.0;
+
+pub fn save_baseline(args: &[String]) -> nih_plug_xtask::Result<()> {
+    let baseline = args.first().map(String::as_str).unwrap_or(DEFAULT_BASELINE);
+    run_benches(&["--save-baseline", baseline])?;
+    println!("Saved benchmark baseline '{baseline}'");
+    Ok(())
+}
+
+pub fn check(args: &[String]) -> nih_plug_xtask::Result<()> {
+    let mut baseline = DEFAULT_BASELINE;
+    let mut threshold_percent = DEFAULT_THRESHOLD_PERCENT;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threshold" => {
+                let value = args.next().context("--threshold needs a percentage")?;
+                threshold_percent = value.parse().with_context(|| format!("'{value}' is not a percentage"))?;
+            }
+            name => baseline = name,
+        }
+    }
+
+    let started = SystemTime::now();
+    run_benches(&["--baseline", baseline])?;
+
+    // Criterion leaves each comparison in `<benchmark>/change/estimates.json`.
+    // Older files are from benchmarks this run did not cover.
+    let criterion_dir = target_dir().join("criterion");
+    let mut changes = Vec::new();
+    find_changes(&criterion_dir, &mut changes)?;
+    changes.retain(|path| path.metadata().and_then(|metadata| metadata.modified()).is_ok_and(|modified| modified >= started));
+    changes.sort();
+    if changes.is_empty() {
+        bail!("no benchmark was compared against '{baseline}', save it first with `cargo xtask bench-baseline {baseline}`");
+    }
+
+    let mut regressions = Vec::new();
+    for path in &changes {
+        let estimates: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)
+            .with_context(|| format!("could not parse {}", path.display()))?;
+        let mean = &estimates["mean"];
+        let (change, lower_bound) = (
+            mean["point_estimate"].as_f64().context("no mean change")?,
+            mean["confidence_interval"]["lower_bound"].as_f64().context("no confidence interval")?,
+        );
+        // `<criterion>/<benchmark id>/change/estimates.json`.
+        let benchmark = path.parent().and_then(Path::parent).and_then(|dir| dir.strip_prefix(&criterion_dir).ok());
+        let benchmark = benchmark.map_or_else(|| path.display().to_string(), |id| id.display().to_string());
+        println!("{benchmark:40} {:+6.1}%", change * 100.0);
+        if lower_bound * 100.0 > threshold_percent {
+            regressions.push(benchmark);
+        }
+    }
+
+    if !regressions.is_empty() {
+        bail!("slower than '{baseline}' by more than {threshold_percent}%: {}", regressions.join(", "));
+    }
+    println!("No benchmark slower than '{baseline}' by more than {threshold_percent}%");
+    Ok(())
+}
+
+fn run_benches(criterion_args: &[&str]) -> nih_plug_xtask::Result<()> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| String::from("cargo"));
+    let status = Command::new(cargo)
+        .current_dir(workspace_root())
+        .args(["bench", "--package", PACKAGE, "--bench", BENCH, "--"])
+        .args(criterion_args)
+        .status()
+        .context("could not run cargo bench")?;
+    if !status.success() {
+        bail!("cargo bench failed");
+    }
+    Ok(())
+}
+
+fn find_changes(dir: &Path, changes: &mut Vec<PathBuf>) -> nih_plug_xtask::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_changes(&path, changes)?;
+        } else if path.ends_with("change/estimates.json") {
+            changes.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf()
+}
+
+fn target_dir() -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR").map_or_else(|| workspace_root().join("target"), PathBuf::from)
+}
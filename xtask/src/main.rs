@@ -1,3 +1,10 @@
+mod bench;
+
 fn main() -> nih_plug_xtask::Result<()> {
-    nih_plug_xtask::main()
-}
\ No newline at end of file
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("bench-baseline") => bench::save_baseline(&args[1..]),
+        Some("bench-check") => bench::check(&args[1..]),
+        _ => nih_plug_xtask::main(),
+    }
+}